@@ -0,0 +1,184 @@
+//! Measures the cost of replaying a level history
+//!
+//! Simulates 60 ghosts recorded over 5 minutes of play and replays
+//! every tick through the replay cursor, then compares it with
+//! scanning the whole history on every tick.
+//!
+//! Then plays 60 ghosts in a headless app and steps its fixed schedule,
+//! which runs the replay systems, the ghosts and the physics.
+//!
+//! Last, seeks to the end of that run, which simulates the whole run again
+//! over several frames.
+//!
+//! Run with `cargo bench --bench replay`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy::app::FixedMain;
+use bevy::prelude::*;
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::player::prelude::*;
+use cycle_of_the_fallen::timeline::prelude::*;
+use cycle_of_the_fallen::timeline::{fast_forward, seek_level};
+use cycle_of_the_fallen::verification::prelude::*;
+
+const GHOSTS: usize = 60;
+const MINUTES: u64 = 5;
+/// Number of ticks between two recorded inputs of a ghost (~60 fps input)
+const INPUT_INTERVAL: u64 = 2;
+/// Number of ticks measured for the full scan, it is too slow to run on the whole level
+const SCAN_TICKS: u64 = 1_000;
+/// Number of ticks stepped in the headless app
+const APP_TICKS: u64 = 60 * TICK_RATE as u64;
+/// Level played in the headless app, its only dummy is never attacked
+const LEVEL: usize = 1;
+
+fn main() {
+    let ticks = MINUTES * 60 * TICK_RATE as u64;
+    let mut history = LevelHistory::<PlayerActionEvent>::default();
+    for tick in (0..ticks).step_by(INPUT_INTERVAL as usize) {
+        for ghost in 0..GHOSTS {
+            history.push(EventRecord {
+                ghost: GhostIdentifier(ghost),
+                tick,
+                event: PlayerActionEvent {
+                    entity: Entity::PLACEHOLDER,
+                    source: EventSource::Replay,
+                    action: PlayerAction {
+                        movement: Vec2::X,
+                        ..default()
+                    },
+                },
+            });
+        }
+    }
+    println!(
+        "{} ghosts, {} minutes, {} ticks, {} records in {:.1} KiB",
+        GHOSTS,
+        MINUTES,
+        ticks,
+        history.len(),
+        history.memory_usage() as f64 / 1024.0
+    );
+
+    let start = Instant::now();
+    let mut replayed = 0;
+    for tick in 0..ticks {
+        replayed += black_box(history.due(tick)).len();
+    }
+    report("cursor", start.elapsed(), ticks, replayed);
+
+    let events = history.events();
+    let start = Instant::now();
+    let mut replayed = 0;
+    for tick in 0..SCAN_TICKS {
+        replayed += events.iter().filter(|r| black_box(r).tick == tick).count();
+    }
+    report("full scan", start.elapsed(), SCAN_TICKS, replayed);
+
+    let (mut app, ghosts) = playing_app();
+    let start = Instant::now();
+    for _ in 0..APP_TICKS {
+        app.world_mut().run_schedule(FixedMain);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:>10}: {ghosts} ghosts over {APP_TICKS} ticks in {elapsed:?} ({:?} per tick)",
+        "app",
+        elapsed / APP_TICKS as u32
+    );
+
+    let mut app = headless_app();
+    app.add_event::<SeekEvent>()
+        .add_systems(Update, (seek_level, fast_forward).chain());
+    load_run(&mut app, &wandering_run()).expect("the bench level exists");
+    app.update();
+    app.world_mut().send_event(SeekEvent { tick: APP_TICKS });
+    let start = Instant::now();
+    let mut frames = 0;
+    let mut longest = Duration::ZERO;
+    loop {
+        let frame = Instant::now();
+        app.update();
+        longest = longest.max(frame.elapsed());
+        frames += 1;
+        if !app.world().contains_resource::<FastForward>() {
+            break;
+        }
+    }
+    println!(
+        "{:>10}: {APP_TICKS} ticks over {frames} frames in {:?} (longest frame {longest:?})",
+        "seek",
+        start.elapsed()
+    );
+}
+
+/// A run of [`GHOSTS`] ghosts that wander without attacking for [`APP_TICKS`]
+fn wandering_run() -> ReplayFile {
+    let mut history = LevelHistory::<PlayerActionEvent>::default();
+    for tick in (0..APP_TICKS).step_by(INPUT_INTERVAL as usize) {
+        for ghost in 0..GHOSTS {
+            // Each ghost walks its own way and turns back every second
+            let turn = if (tick / TICK_RATE as u64) % 2 == 0 {
+                1.0
+            } else {
+                -1.0
+            };
+            let movement = Vec2::from_angle(ghost as f32) * turn;
+            history.push(EventRecord {
+                ghost: GhostIdentifier(ghost),
+                tick,
+                event: PlayerActionEvent {
+                    entity: Entity::PLACEHOLDER,
+                    source: EventSource::Replay,
+                    action: PlayerAction {
+                        movement,
+                        aim: movement,
+                        ..default()
+                    },
+                },
+            });
+        }
+    }
+    let ghost_list = PlayerGhostList {
+        ghosts: (0..GHOSTS)
+            .map(|ghost| PlayerGhost {
+                entity: None,
+                class: [Class::Knight, Class::Ranger, Class::Wizard][ghost % 3],
+                muted: false,
+            })
+            .collect(),
+        forked: None,
+        forked_at: 0,
+    };
+    let mut file = ReplayFile::new(LEVEL, &ghost_list);
+    file.write_history(&history);
+    file
+}
+
+/// A headless app playing the [`wandering_run`], with the number of ghosts spawned
+fn playing_app() -> (App, usize) {
+    let mut app = headless_app();
+    load_run(&mut app, &wandering_run()).expect("the bench level exists");
+    // Enters the level and spawns the ghosts
+    app.update();
+    let ghosts = app
+        .world()
+        .resource::<PlayerGhostList>()
+        .ghosts
+        .iter()
+        .filter(|ghost| ghost.entity.is_some())
+        .count();
+    // Runs the fixed schedule with the fixed clock, like the fixed main loop does
+    let time = app.world().resource::<Time<Fixed>>().as_generic();
+    *app.world_mut().resource_mut::<Time>() = time;
+    (app, ghosts)
+}
+
+fn report(name: &str, elapsed: Duration, ticks: u64, replayed: usize) {
+    println!(
+        "{name:>10}: {replayed} events over {ticks} ticks in {elapsed:?} ({:?} per tick)",
+        elapsed / ticks as u32
+    );
+}
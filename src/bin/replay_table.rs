@@ -0,0 +1,80 @@
+//! Converts saved runs to tables for analysis, and tables back to runs
+//!
+//! Usage:
+//! - `cargo run --bin replay_table -- export saves/level_1_win.replay level_1.csv`
+//! - `cargo run --bin replay_table -- import level_1.jsonl saves/level_1.replay`
+//!
+//! The format of the table is picked from its extension, `.csv` or `.jsonl`.
+//! Each row is one record with its ghost, class, tick, time, event kind and payload.
+//! Each ghost also has a row of kind `ghost` with its class and whether it is muted.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::player::prelude::*;
+
+/// The event types the simulation records, to read and write every section of replay files
+fn recorded_events() -> RecordedEvents {
+    let mut recorded = RecordedEvents::default();
+    add_player_recorded_events(&mut recorded);
+    recorded
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+}
+
+fn export(from: &Path, to: &Path) -> Result<usize, ReplayError> {
+    let recorded = recorded_events();
+    let rows = recorded.to_rows(&ReplayFile::load(from)?)?;
+    let writer = BufWriter::new(File::create(to)?);
+    if is_csv(to) {
+        write_csv(&rows, &recorded.csv_columns(), writer)?;
+    } else {
+        write_jsonl(&rows, writer)?;
+    }
+    Ok(rows.len())
+}
+
+fn import(from: &Path, to: &Path) -> Result<usize, ReplayError> {
+    let text = std::fs::read_to_string(from)?;
+    let rows = if is_csv(from) {
+        read_csv(&text)?
+    } else {
+        read_jsonl(&text)?
+    };
+    recorded_events().from_rows(&rows)?.save(to)?;
+    Ok(rows.len())
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [command, from, to] = args.as_slice() else {
+        eprintln!("Usage: replay_table <export|import> <from> <to>");
+        return ExitCode::from(2);
+    };
+    let (from, to) = (PathBuf::from(from), PathBuf::from(to));
+
+    let result = match command.as_str() {
+        "export" => export(&from, &to),
+        "import" => import(&from, &to),
+        _ => {
+            eprintln!("Unknown command {command}, expected export or import");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(records) => {
+            println!("Wrote {records} records to {}", to.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Could not {command} {}: {e}", from.display());
+            ExitCode::FAILURE
+        }
+    }
+}
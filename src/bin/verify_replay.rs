@@ -0,0 +1,34 @@
+//! Plays a saved run without window nor rendering and reports its outcome
+//!
+//! Usage: `cargo run --bin verify_replay -- saves/level_1_win.replay`
+//!
+//! Exits with 0 when the level is completed, 1 when it is not
+//! and 2 when the replay file cannot be read.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::verification::prelude::*;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1).map(PathBuf::from) else {
+        eprintln!("Usage: verify_replay <replay file>");
+        return ExitCode::from(2);
+    };
+
+    match ReplayFile::load(&path).and_then(|file| verify_run(&file)) {
+        Ok(report) => {
+            print!("{report}");
+            if report.completed() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("Could not verify {}: {e}", path.display());
+            ExitCode::from(2)
+        }
+    }
+}
@@ -1,10 +1,19 @@
 use bevy::prelude::*;
 
+use crate::level_history::prelude::EditGhostEvent;
 use crate::Class;
 
 #[derive(Component)]
 pub struct CharacterSelectionButton(pub Class);
 
+/// Root node of the list of the ghosts of the level
+#[derive(Component)]
+pub struct GhostPanel;
+
+/// A button of the ghost panel, it sends its edit when pressed
+#[derive(Component)]
+pub struct GhostPanelButton(pub EditGhostEvent);
+
 #[derive(Resource)]
 pub struct SelectedCharacter(pub Class);
 
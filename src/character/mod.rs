@@ -2,10 +2,11 @@ mod data;
 mod systems;
 
 use bevy::prelude::*;
-use data::SelectedCharacter;
+use data::{GhostPanel, SelectedCharacter};
 use systems::*;
 
 use crate::game::GameState;
+use crate::level_history::prelude::PlayerGhostList;
 
 pub mod prelude {
     pub use super::data::*;
@@ -22,7 +23,15 @@ impl Plugin for CharactersPlugin {
             )
             .add_systems(
                 Update,
-                (interaction_on_character_selection_buttons)
+                (
+                    interaction_on_character_selection_buttons,
+                    interaction_on_ghost_panel_buttons,
+                    character_selection_keys,
+                    spawn_ghost_panel.run_if(
+                        resource_changed::<PlayerGhostList>
+                            .or_else(not(any_with_component::<GhostPanel>)),
+                    ),
+                )
                     .run_if(in_state(GameState::CharacterSelection)),
             );
     }
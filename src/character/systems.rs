@@ -1,15 +1,34 @@
 use bevy::{color::palettes::tailwind, prelude::*};
 
 use crate::game::{CurrentLevel, GameState};
+use crate::level_history::prelude::*;
 
-use super::prelude::{CharacterSelectionButton, SelectedCharacter};
+use super::prelude::{CharacterSelectionButton, GhostPanel, GhostPanelButton, SelectedCharacter};
 
 pub fn spawn_character_selection(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     current_level: Res<CurrentLevel>,
+    casualties: Res<GhostCasualties>,
+    ghost_list: Res<PlayerGhostList>,
 ) {
     info!("Spawn CharacterSelection");
+    let casualties = casualties
+        .0
+        .iter()
+        .map(|c| {
+            let class = ghost_list
+                .get_ghost(c.ghost)
+                .map(|g| format!("{:?}", g.class))
+                .unwrap_or_default();
+            format!(
+                "{} {class} {} at {:.1}s",
+                c.ghost.0 + 1,
+                c.killer,
+                LevelTick(c.tick).as_secs()
+            )
+        })
+        .collect::<Vec<_>>();
     commands
         .spawn((
             StateScoped(GameState::CharacterSelection),
@@ -40,6 +59,39 @@ pub fn spawn_character_selection(
                     ..default()
                 },
             ));
+            wrapper.spawn(
+                TextBundle::from_section(
+                    "Escape: back to the levels    Z: undo the last cycle    Backspace: reset the ghosts of this level",
+                    TextStyle {
+                        font: asset_server.load("Kalam-Light.ttf"),
+                        font_size: 24.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(20.0),
+                    ..default()
+                }),
+            );
+            if !casualties.is_empty() {
+                wrapper.spawn(
+                    TextBundle::from_section(
+                        format!("Casualties of the last cycle\n{}", casualties.join("\n")),
+                        TextStyle {
+                            font: asset_server.load("Kalam-Light.ttf"),
+                            font_size: 20.0,
+                            ..default()
+                        },
+                    )
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(20.0),
+                        left: Val::Px(20.0),
+                        ..default()
+                    }),
+                );
+            }
             let characters =
                 current_level.0.clone().map(|l| l.characters).expect(
                     "Expected a current level to be set in system spawn_character_selection",
@@ -58,7 +110,7 @@ pub fn spawn_character_selection(
                             },
                             ..default()
                         },
-                        CharacterSelectionButton(character.clone()),
+                        CharacterSelectionButton(*character),
                     ))
                     .with_children(|selector| {
                         selector.spawn(TextBundle::from_section(
@@ -90,7 +142,174 @@ pub fn interaction_on_character_selection_buttons(
         *background_color = match *interaction {
             Interaction::Pressed => {
                 game_state.set(GameState::Play);
-                selected_character.set(character_selection_button.0.clone());
+                selected_character.set(character_selection_button.0);
+                tailwind::LIME_300.into()
+            }
+            Interaction::Hovered => tailwind::LIME_500.into(),
+            Interaction::None => tailwind::LIME_800.into(),
+        }
+    }
+}
+
+/// Goes back to the level selection with Escape, Z undoes the last cycle
+/// and Backspace wipes the ghosts of the level
+pub fn character_selection_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    current_level: Res<CurrentLevel>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut reset_event: EventWriter<ResetLevelEvent>,
+    mut edit_event: EventWriter<EditGhostEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_state.set(GameState::LevelSelection);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyZ) {
+        edit_event.send(EditGhostEvent::UndoCycle);
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        if let Some(level) = &current_level.0 {
+            reset_event.send(ResetLevelEvent { level: level.id });
+        }
+    }
+}
+
+fn ghost_panel_button(
+    row: &mut WorldChildBuilder,
+    label: &str,
+    edit: EditGhostEvent,
+    font: &Handle<Font>,
+) {
+    row.spawn((
+        GhostPanelButton(edit),
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                ..default()
+            },
+            background_color: tailwind::LIME_800.into(),
+            ..default()
+        },
+    ))
+    .with_children(|button| {
+        button.spawn(TextBundle::from_section(
+            label,
+            TextStyle {
+                font: font.clone(),
+                font_size: 18.0,
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Lists the ghosts of the level with their class and recording length,
+/// each can be muted, moved before the previous one or deleted
+///
+/// The panel is rebuilt whenever the ghosts change
+pub fn spawn_ghost_panel(world: &mut World) {
+    let panels = world
+        .query_filtered::<Entity, With<GhostPanel>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for panel in panels {
+        world.entity_mut(panel).despawn_recursive();
+    }
+
+    let ghosts = world
+        .resource::<PlayerGhostList>()
+        .ghosts
+        .iter()
+        .map(|g| (g.class, g.muted))
+        .collect::<Vec<_>>();
+    let last_ticks = world
+        .resource::<RecordedEvents>()
+        .clone()
+        .last_ticks(world, ghosts.len());
+    let font = world.resource::<AssetServer>().load("Kalam-Light.ttf");
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 20.0,
+        ..default()
+    };
+
+    world
+        .spawn((
+            GhostPanel,
+            StateScoped(GameState::CharacterSelection),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            },
+        ))
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section("Ghosts", text_style.clone()));
+            if ghosts.is_empty() {
+                panel.spawn(TextBundle::from_section(
+                    "No cycle recorded yet",
+                    text_style.clone(),
+                ));
+            }
+            for (index, ((class, muted), last_tick)) in ghosts.iter().zip(last_ticks).enumerate() {
+                let ghost = GhostIdentifier(index);
+                let mut style = text_style.clone();
+                if *muted {
+                    style.color = Color::srgba(1.0, 1.0, 1.0, 0.4);
+                }
+                panel
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(
+                            TextBundle::from_section(
+                                format!(
+                                    "{} {class:?} {:.1}s",
+                                    index + 1,
+                                    LevelTick(last_tick).as_secs()
+                                ),
+                                style,
+                            )
+                            .with_style(Style {
+                                width: Val::Px(140.0),
+                                ..default()
+                            }),
+                        );
+                        let mute = if *muted { "Unmute" } else { "Mute" };
+                        ghost_panel_button(row, mute, EditGhostEvent::ToggleMute(ghost), &font);
+                        if index > 0 {
+                            ghost_panel_button(row, "Up", EditGhostEvent::MoveUp(ghost), &font);
+                        }
+                        ghost_panel_button(row, "Delete", EditGhostEvent::Delete(ghost), &font);
+                    });
+            }
+            if !ghosts.is_empty() {
+                ghost_panel_button(panel, "Undo last cycle", EditGhostEvent::UndoCycle, &font);
+            }
+        });
+}
+
+pub fn interaction_on_ghost_panel_buttons(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &GhostPanelButton), Changed<Interaction>>,
+    mut edit_event: EventWriter<EditGhostEvent>,
+) {
+    for (interaction, mut background_color, button) in query.iter_mut() {
+        *background_color = match *interaction {
+            Interaction::Pressed => {
+                edit_event.send(button.0);
                 tailwind::LIME_300.into()
             }
             Interaction::Hovered => tailwind::LIME_500.into(),
@@ -0,0 +1,238 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+
+use crate::ennemy::prelude::*;
+use crate::levels::prelude::*;
+use crate::player::prelude::*;
+use crate::walls::prelude::*;
+
+/// Spacing of the grid the edited things snap to
+pub const GRID_SIZE: f32 = 20.0;
+
+/// Distance from a handle of the selected wall at which it is reshaped
+pub const HANDLE_RADIUS: f32 = 8.0;
+
+/// Width of the panel on the right of the editor, the mouse does not edit behind it
+pub const PANEL_WIDTH: f32 = 240.0;
+
+/// Half the size of the objective markers
+pub const OBJECTIVE_RADIUS: f32 = 12.0;
+
+/// Angle a wall turns with Q and E
+pub const ROTATION_STEP: f32 = FRAC_PI_4 / 3.0;
+
+/// The level being edited, it is kept while it is playtested
+#[derive(Resource, Debug, Clone)]
+pub struct EditedLevel {
+    pub definition: LevelDefinition,
+    /// The file of the level in the assets
+    pub path: String,
+    /// None until a new level is saved
+    pub handle: Option<Handle<LevelDefinition>>,
+    /// Whether the file holds the last changes
+    pub saved: bool,
+}
+
+impl EditedLevel {
+    /// An empty arena, playable by every class
+    pub fn new_level(id: usize) -> Self {
+        EditedLevel {
+            definition: LevelDefinition {
+                id,
+                arena: Vec2::new(1000.0, 600.0),
+                walls: vec![],
+                ennemies: vec![],
+                spawn: Vec2::new(-400.0, 0.0),
+                objectives: vec![],
+                characters: vec![Class::Knight, Class::Ranger, Class::Wizard],
+                requires: vec![],
+            },
+            path: format!("levels/{id}.level.ron"),
+            handle: None,
+            saved: false,
+        }
+    }
+
+    /// The thing under a point, the smallest ones first
+    pub fn item_at(&self, point: Vec2) -> Option<EditorItem> {
+        let level = &self.definition;
+        if level.spawn.distance(point) <= PLAYER_RADIUS {
+            return Some(EditorItem::Spawn);
+        }
+        if let Some(index) = level
+            .objectives
+            .iter()
+            .position(|o| o.distance(point) <= OBJECTIVE_RADIUS)
+        {
+            return Some(EditorItem::Objective(index));
+        }
+        if let Some(index) = level
+            .ennemies
+            .iter()
+            .position(|e| e.position.distance(point) <= e.kind.radius())
+        {
+            return Some(EditorItem::Ennemy(index));
+        }
+        level
+            .walls
+            .iter()
+            .rposition(|wall| wall.contains(point))
+            .map(EditorItem::Wall)
+    }
+
+    pub fn position(&self, item: EditorItem) -> Option<Vec2> {
+        let level = &self.definition;
+        match item {
+            EditorItem::Wall(index) => level.walls.get(index).map(WallShape::position),
+            EditorItem::Ennemy(index) => level.ennemies.get(index).map(|e| e.position),
+            EditorItem::Spawn => Some(level.spawn),
+            EditorItem::Objective(index) => level.objectives.get(index).copied(),
+        }
+    }
+
+    pub fn set_position(&mut self, item: EditorItem, position: Vec2) {
+        let level = &mut self.definition;
+        match item {
+            EditorItem::Wall(index) => {
+                if let Some(wall) = level.walls.get_mut(index) {
+                    wall.translate(position - wall.position());
+                }
+            }
+            EditorItem::Ennemy(index) => {
+                if let Some(ennemy) = level.ennemies.get_mut(index) {
+                    ennemy.position = position;
+                }
+            }
+            EditorItem::Spawn => level.spawn = position,
+            EditorItem::Objective(index) => {
+                if let Some(objective) = level.objectives.get_mut(index) {
+                    *objective = position;
+                }
+            }
+        }
+    }
+
+    /// Removes a thing, the spawn point cannot be removed
+    pub fn remove(&mut self, item: EditorItem) {
+        let level = &mut self.definition;
+        match item {
+            EditorItem::Wall(index) if index < level.walls.len() => {
+                level.walls.remove(index);
+            }
+            EditorItem::Ennemy(index) if index < level.ennemies.len() => {
+                level.ennemies.remove(index);
+            }
+            EditorItem::Objective(index) if index < level.objectives.len() => {
+                level.objectives.remove(index);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A thing of the edited level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorItem {
+    Wall(usize),
+    Ennemy(usize),
+    Spawn,
+    Objective(usize),
+}
+
+/// What the left click does
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EditorTool {
+    /// Selects, moves and reshapes the things
+    #[default]
+    Select,
+    /// Draws a wall from one corner to the other
+    Wall,
+    Circle,
+    /// Draws a line of cover, pressing the end of the selected one extends it
+    Line,
+    /// Places a square to reshape by its points
+    Polygon,
+    Ennemy(EnnemyKind),
+    Spawn,
+    Objective,
+}
+
+impl EditorTool {
+    pub const ALL: [EditorTool; 9] = [
+        EditorTool::Select,
+        EditorTool::Wall,
+        EditorTool::Circle,
+        EditorTool::Line,
+        EditorTool::Polygon,
+        EditorTool::Ennemy(EnnemyKind::Dummy),
+        EditorTool::Ennemy(EnnemyKind::Turret),
+        EditorTool::Spawn,
+        EditorTool::Objective,
+    ];
+
+    /// Keys picking the tools, in the order of [`EditorTool::ALL`]
+    pub const KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            EditorTool::Ennemy(kind) => format!("{kind:?}"),
+            tool => format!("{tool:?}"),
+        }
+    }
+}
+
+/// What the mouse is doing with the selected thing
+#[derive(Debug, Clone, Copy)]
+pub enum EditorDrag {
+    /// Keeps the offset between the thing and the grid point it was grabbed at
+    Move { offset: Vec2 },
+    /// Moves a handle of a wall, the opposite corner of a rectangle stays at the anchor
+    Reshape { handle: usize, anchor: Vec2 },
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct EditorSelection {
+    pub item: Option<EditorItem>,
+    pub drag: Option<EditorDrag>,
+}
+
+/// Present while a level is played from the editor, F6, a win or a death go back to it
+///
+/// The run is kept out of the scores and the ghosts by [`UnscoredRun`](crate::game::UnscoredRun)
+#[derive(Resource, Debug)]
+pub struct Playtest;
+
+/// Root node of the panel of the editor
+#[derive(Component)]
+pub struct EditorPanel;
+
+#[derive(Component)]
+pub struct EditorText;
+
+/// What a button of the editor panel does when pressed
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorButton {
+    Tool(EditorTool),
+    /// Allows or forbids a class in the level
+    Class(Class),
+    Save,
+    Playtest,
+    Back,
+}
+
+/// Opens the editor from the level selection, a button without level makes a new one
+#[derive(Component, Debug)]
+pub struct EditLevelButton {
+    pub level: Option<usize>,
+}
@@ -0,0 +1,9 @@
+use bevy::prelude::*;
+
+/// Writes the edited level to its file and applies it to the game
+#[derive(Event, Debug, Clone)]
+pub struct SaveLevelEvent;
+
+/// Saves the edited level and plays it
+#[derive(Event, Debug, Clone)]
+pub struct PlaytestEvent;
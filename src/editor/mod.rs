@@ -0,0 +1,67 @@
+mod data;
+mod events;
+mod systems;
+
+use bevy::prelude::*;
+use data::*;
+use events::*;
+use systems::*;
+
+use crate::game::{GameState, PlayState};
+
+pub mod prelude {
+    pub use super::data::*;
+    pub use super::events::*;
+    pub use super::EditorPlugin;
+}
+
+/// Edits the levels with the mouse on a grid, saves them to their files
+/// and plays them from the editor
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveLevelEvent>()
+            .add_event::<PlaytestEvent>()
+            .init_resource::<EditorTool>()
+            .init_resource::<EditorSelection>()
+            .add_systems(
+                OnEnter(GameState::LevelSelection),
+                (spawn_edit_buttons, clear_edited_level),
+            )
+            .add_systems(
+                Update,
+                interaction_on_edit_buttons.run_if(in_state(GameState::LevelSelection)),
+            )
+            .add_systems(
+                OnEnter(GameState::Editor),
+                (
+                    discard_playtest_run,
+                    reset_editor_selection,
+                    spawn_editor_panel,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    interaction_on_editor_buttons,
+                    editor_shortcuts,
+                    edit_with_mouse,
+                    save_edited_level,
+                    start_playtest,
+                    color_editor_buttons,
+                    update_editor_text,
+                    draw_editor,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editor)),
+            )
+            .add_systems(
+                Update,
+                (end_playtest, back_to_editor)
+                    .chain()
+                    .run_if(in_state(PlayState::Running))
+                    .run_if(resource_exists::<Playtest>),
+            );
+    }
+}
@@ -0,0 +1,810 @@
+use std::io;
+use std::path::PathBuf;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::{color::palettes::tailwind, prelude::*, window::PrimaryWindow};
+
+use super::events::*;
+use super::prelude::*;
+use crate::character::prelude::SelectedCharacter;
+use crate::ennemy::prelude::*;
+use crate::game::{CurrentLevel, GameState, Level, Levels, UnscoredRun};
+use crate::level_history::prelude::*;
+use crate::levels::prelude::*;
+use crate::player::prelude::*;
+use crate::walls::prelude::*;
+
+fn edit_button(column: &mut ChildBuilder, label: String, button: impl Bundle, font: Handle<Font>) {
+    column
+        .spawn((
+            button,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+                    ..default()
+                },
+                background_color: tailwind::LIME_800.into(),
+                ..default()
+            },
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font,
+                    font_size: 20.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Lists the levels that can be edited, and a new one, in a corner of the level selection
+pub fn spawn_edit_buttons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+) {
+    let font = asset_server.load("Kalam-Light.ttf");
+    commands
+        .spawn((
+            StateScoped(GameState::LevelSelection),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20.0),
+                    left: Val::Px(20.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|column| {
+            for level in levels.iter() {
+                edit_button(
+                    column,
+                    format!("Edit level {}", level.id),
+                    EditLevelButton {
+                        level: Some(level.id),
+                    },
+                    font.clone(),
+                );
+            }
+            edit_button(
+                column,
+                "New level".to_string(),
+                EditLevelButton { level: None },
+                font.clone(),
+            );
+        });
+}
+
+pub fn interaction_on_edit_buttons(
+    mut commands: Commands,
+    mut query: Query<(&Interaction, &mut BackgroundColor, &EditLevelButton), Changed<Interaction>>,
+    levels: Res<Levels>,
+    definitions: Res<Assets<LevelDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut background_color, button) in query.iter_mut() {
+        *background_color = match *interaction {
+            Interaction::Pressed => {
+                let level = button.level.map(|id| levels.id(id));
+                let edited = match &level {
+                    Some(level) => {
+                        definitions
+                            .get(&level.definition)
+                            .map(|definition| EditedLevel {
+                                definition: definition.clone(),
+                                path: asset_server
+                                    .get_path(&level.definition)
+                                    .map(|path| path.path().to_string_lossy().into_owned())
+                                    .unwrap_or_else(|| format!("levels/{}.level.ron", level.id)),
+                                handle: Some(level.definition.clone()),
+                                saved: true,
+                            })
+                    }
+                    None => {
+                        let id = levels.iter().map(|l| l.id).max().unwrap_or_default() + 1;
+                        Some(EditedLevel::new_level(id))
+                    }
+                };
+                if let Some(edited) = edited {
+                    info!("Editing level {}", edited.definition.id);
+                    commands.insert_resource(edited);
+                    current_level.0 = level;
+                    game_state.set(GameState::Editor);
+                }
+                tailwind::LIME_300.into()
+            }
+            Interaction::Hovered => tailwind::LIME_500.into(),
+            Interaction::None => tailwind::LIME_800.into(),
+        }
+    }
+}
+
+pub fn clear_edited_level(mut commands: Commands) {
+    commands.remove_resource::<EditedLevel>();
+    commands.remove_resource::<Playtest>();
+    commands.remove_resource::<UnscoredRun>();
+}
+
+/// Forgets the run of a playtest left for the editor, it does not become a ghost
+///
+/// A ghost taken over during the playtest gets its records back
+pub fn discard_playtest_run(world: &mut World) {
+    let mut ghost_list = world.resource_mut::<PlayerGhostList>();
+    let live = ghost_list.live();
+    let tick = match ghost_list.forked.take() {
+        Some(_) => ghost_list.forked_at,
+        None => 0,
+    };
+    let recorded_events = world.resource::<RecordedEvents>().clone();
+    match world.resource_mut::<ForkedRecords>().0.take() {
+        Some(stash) => recorded_events.restore_ghost(world, stash),
+        None => recorded_events.discard_from(world, live, tick),
+    }
+}
+
+pub fn reset_editor_selection(mut selection: ResMut<EditorSelection>) {
+    *selection = EditorSelection::default();
+}
+
+/// Spawns the panel with the tools, the classes of the level and the file actions
+pub fn spawn_editor_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    edited: Res<EditedLevel>,
+) {
+    let font = asset_server.load("Kalam-Light.ttf");
+    let title = |text: &str| {
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font: asset_server.load("Kalam-Bold.ttf"),
+                font_size: 24.0,
+                ..default()
+            },
+        )
+    };
+
+    commands
+        .spawn((
+            EditorPanel,
+            StateScoped(GameState::Editor),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(0.0),
+                    width: Val::Px(PANEL_WIDTH),
+                    height: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|panel| {
+            panel.spawn(title(&format!("Level {}", edited.definition.id)));
+            panel.spawn(title("Tools"));
+            for (tool, key) in EditorTool::ALL.into_iter().zip(1..) {
+                edit_button(
+                    panel,
+                    format!("{key}: {}", tool.name()),
+                    EditorButton::Tool(tool),
+                    font.clone(),
+                );
+            }
+            panel.spawn(title("Classes"));
+            for class in [Class::Knight, Class::Ranger, Class::Wizard] {
+                edit_button(
+                    panel,
+                    format!("{class:?}"),
+                    EditorButton::Class(class),
+                    font.clone(),
+                );
+            }
+            panel.spawn(title("Level"));
+            edit_button(panel, "Save".to_string(), EditorButton::Save, font.clone());
+            edit_button(
+                panel,
+                "Playtest".to_string(),
+                EditorButton::Playtest,
+                font.clone(),
+            );
+            edit_button(panel, "Back".to_string(), EditorButton::Back, font.clone());
+            panel.spawn((
+                EditorText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+pub fn interaction_on_editor_buttons(
+    query: Query<(&Interaction, &EditorButton), Changed<Interaction>>,
+    mut tool: ResMut<EditorTool>,
+    mut edited: ResMut<EditedLevel>,
+    mut save_event: EventWriter<SaveLevelEvent>,
+    mut playtest_event: EventWriter<PlaytestEvent>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            EditorButton::Tool(pressed) => *tool = pressed,
+            EditorButton::Class(class) => {
+                let characters = &mut edited.definition.characters;
+                match characters.iter().position(|c| *c == class) {
+                    Some(index) => {
+                        characters.remove(index);
+                    }
+                    None => characters.push(class),
+                }
+                edited.saved = false;
+            }
+            EditorButton::Save => {
+                save_event.send(SaveLevelEvent);
+            }
+            EditorButton::Playtest => {
+                save_event.send(SaveLevelEvent);
+                playtest_event.send(PlaytestEvent);
+            }
+            EditorButton::Back => game_state.set(GameState::LevelSelection),
+        }
+    }
+}
+
+/// Colors the buttons of the current tool and of the allowed classes brighter
+pub fn color_editor_buttons(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &EditorButton)>,
+    tool: Res<EditorTool>,
+    edited: Res<EditedLevel>,
+) {
+    for (interaction, mut background_color, button) in query.iter_mut() {
+        let active = match button {
+            EditorButton::Tool(t) => *t == *tool,
+            EditorButton::Class(class) => edited.definition.characters.contains(class),
+            _ => false,
+        };
+        *background_color = match (*interaction, active) {
+            (Interaction::Pressed, _) => tailwind::LIME_300.into(),
+            (Interaction::Hovered, _) => tailwind::LIME_500.into(),
+            (Interaction::None, true) => tailwind::LIME_600.into(),
+            (Interaction::None, false) => tailwind::LIME_800.into(),
+        };
+    }
+}
+
+/// 1-9 pick the tools, Delete removes the selection, Q and E turn the selected wall,
+/// Ctrl+S saves, F6 playtests and Escape goes back to the level selection
+pub fn editor_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tool: ResMut<EditorTool>,
+    mut selection: ResMut<EditorSelection>,
+    mut edited: ResMut<EditedLevel>,
+    mut save_event: EventWriter<SaveLevelEvent>,
+    mut playtest_event: EventWriter<PlaytestEvent>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (key, pressed) in EditorTool::KEYS.iter().zip(EditorTool::ALL) {
+        if keyboard_input.just_pressed(*key) {
+            *tool = pressed;
+            selection.drag = None;
+        }
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        if let Some(item) = selection.item.take() {
+            edited.remove(item);
+            edited.saved = false;
+        }
+    }
+
+    let turn = match (
+        keyboard_input.just_pressed(KeyCode::KeyQ),
+        keyboard_input.just_pressed(KeyCode::KeyE),
+    ) {
+        (true, false) => ROTATION_STEP,
+        (false, true) => -ROTATION_STEP,
+        _ => 0.0,
+    };
+    if let (Some(EditorItem::Wall(index)), true) = (selection.item, turn != 0.0) {
+        if let Some(wall) = edited.definition.walls.get_mut(index) {
+            wall.rotate(turn);
+            edited.saved = false;
+        }
+    }
+
+    let control = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if control && keyboard_input.just_pressed(KeyCode::KeyS) {
+        save_event.send(SaveLevelEvent);
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        save_event.send(SaveLevelEvent);
+        playtest_event.send(PlaytestEvent);
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_state.set(GameState::LevelSelection);
+    }
+}
+
+fn snap(point: Vec2) -> Vec2 {
+    (point / GRID_SIZE).round() * GRID_SIZE
+}
+
+/// Points of a wall the mouse drags to reshape it
+fn wall_handles(wall: &WallShape) -> Vec<Vec2> {
+    match wall {
+        WallShape::Rectangle {
+            position,
+            size,
+            rotation,
+        } => {
+            let rotation = Rot2::radians(*rotation);
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .map(|(x, y)| *position + rotation * (Vec2::new(x, y) * *size / 2.0))
+                .to_vec()
+        }
+        WallShape::Circle { position, radius } => vec![*position + Vec2::X * *radius],
+        WallShape::Polygon { points } | WallShape::Polyline { points, .. } => points.clone(),
+    }
+}
+
+/// Moves a handle of a wall to a point, a rectangle keeps the corner at the anchor
+fn reshape_wall(wall: &WallShape, handle: usize, anchor: Vec2, point: Vec2) -> WallShape {
+    let mut wall = wall.clone();
+    match &mut wall {
+        WallShape::Rectangle {
+            position,
+            size,
+            rotation,
+        } => {
+            let rotation = Rot2::radians(*rotation);
+            let diagonal = rotation.inverse() * (point - anchor);
+            *size = diagonal.abs().max(Vec2::splat(GRID_SIZE));
+            let half = Vec2::new(size.x.copysign(diagonal.x), size.y.copysign(diagonal.y)) / 2.0;
+            *position = anchor + rotation * half;
+        }
+        WallShape::Circle { position, radius } => {
+            *radius = position.distance(point).max(GRID_SIZE / 2.0);
+        }
+        WallShape::Polygon { points } | WallShape::Polyline { points, .. } => {
+            if let Some(p) = points.get_mut(handle) {
+                *p = point;
+            }
+        }
+    }
+    wall
+}
+
+/// Places, selects, moves and reshapes with the left button, removes with the right one
+pub fn edit_with_mouse(
+    mouse: Res<ButtonInput<MouseButton>>,
+    query_window: Query<&Window, With<PrimaryWindow>>,
+    query_camera: Query<(&Camera, &GlobalTransform)>,
+    tool: Res<EditorTool>,
+    mut selection: ResMut<EditorSelection>,
+    mut edited: ResMut<EditedLevel>,
+) {
+    let window = query_window.single();
+    let (camera, camera_transform) = query_camera.single();
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let Some(point) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+    let grid_point = snap(point);
+    let over_panel = cursor.x > window.width() - PANEL_WIDTH;
+
+    if mouse.just_pressed(MouseButton::Left) && !over_panel {
+        match *tool {
+            EditorTool::Select => {
+                let handle = match selection.item {
+                    Some(EditorItem::Wall(index)) => edited.definition.walls.get(index),
+                    _ => None,
+                }
+                .and_then(|wall| {
+                    wall_handles(wall)
+                        .into_iter()
+                        .enumerate()
+                        .find(|(_, handle)| handle.distance(point) <= HANDLE_RADIUS)
+                        .map(|(handle, position)| EditorDrag::Reshape {
+                            handle,
+                            anchor: 2.0 * wall.position() - position,
+                        })
+                });
+                if handle.is_some() {
+                    selection.drag = handle;
+                } else {
+                    selection.item = edited.item_at(point);
+                    selection.drag =
+                        selection
+                            .item
+                            .and_then(|item| edited.position(item))
+                            .map(|position| EditorDrag::Move {
+                                offset: position - grid_point,
+                            });
+                }
+            }
+            EditorTool::Wall => {
+                let walls = &mut edited.definition.walls;
+                walls.push(WallShape::Rectangle {
+                    position: grid_point + Vec2::splat(GRID_SIZE / 2.0),
+                    size: Vec2::splat(GRID_SIZE),
+                    rotation: 0.0,
+                });
+                selection.item = Some(EditorItem::Wall(walls.len() - 1));
+                selection.drag = Some(EditorDrag::Reshape {
+                    handle: 2,
+                    anchor: grid_point,
+                });
+                edited.saved = false;
+            }
+            EditorTool::Circle => {
+                let walls = &mut edited.definition.walls;
+                walls.push(WallShape::Circle {
+                    position: grid_point,
+                    radius: GRID_SIZE,
+                });
+                selection.item = Some(EditorItem::Wall(walls.len() - 1));
+                selection.drag = Some(EditorDrag::Reshape {
+                    handle: 0,
+                    anchor: grid_point,
+                });
+                edited.saved = false;
+            }
+            EditorTool::Line => {
+                let selected = match selection.item {
+                    Some(EditorItem::Wall(index)) => Some(index),
+                    _ => None,
+                };
+                let walls = &mut edited.definition.walls;
+                let extended = selected.and_then(|index| match walls.get_mut(index) {
+                    Some(WallShape::Polyline { points, .. })
+                        if points
+                            .last()
+                            .is_some_and(|p| p.distance(point) <= HANDLE_RADIUS) =>
+                    {
+                        points.push(grid_point);
+                        Some((index, points.len() - 1))
+                    }
+                    _ => None,
+                });
+                let (index, handle) = extended.unwrap_or_else(|| {
+                    walls.push(WallShape::Polyline {
+                        points: vec![grid_point, grid_point],
+                        width: WALL_WIDTH,
+                    });
+                    (walls.len() - 1, 1)
+                });
+                selection.item = Some(EditorItem::Wall(index));
+                selection.drag = Some(EditorDrag::Reshape {
+                    handle,
+                    anchor: grid_point,
+                });
+                edited.saved = false;
+            }
+            EditorTool::Polygon => {
+                let walls = &mut edited.definition.walls;
+                walls.push(WallShape::Polygon {
+                    points: [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]
+                        .map(|(x, y)| grid_point + Vec2::new(x, y) * GRID_SIZE)
+                        .to_vec(),
+                });
+                selection.item = Some(EditorItem::Wall(walls.len() - 1));
+                selection.drag = Some(EditorDrag::Move {
+                    offset: Vec2::splat(GRID_SIZE),
+                });
+                edited.saved = false;
+            }
+            EditorTool::Ennemy(kind) => {
+                let ennemies = &mut edited.definition.ennemies;
+                ennemies.push(EnnemyPlacement {
+                    kind,
+                    position: grid_point,
+                });
+                selection.item = Some(EditorItem::Ennemy(ennemies.len() - 1));
+                selection.drag = Some(EditorDrag::Move { offset: Vec2::ZERO });
+                edited.saved = false;
+            }
+            EditorTool::Spawn => {
+                edited.definition.spawn = grid_point;
+                selection.item = Some(EditorItem::Spawn);
+                selection.drag = Some(EditorDrag::Move { offset: Vec2::ZERO });
+                edited.saved = false;
+            }
+            EditorTool::Objective => {
+                let objectives = &mut edited.definition.objectives;
+                objectives.push(grid_point);
+                selection.item = Some(EditorItem::Objective(objectives.len() - 1));
+                selection.drag = Some(EditorDrag::Move { offset: Vec2::ZERO });
+                edited.saved = false;
+            }
+        }
+    } else if mouse.pressed(MouseButton::Left) {
+        match (selection.item, selection.drag) {
+            (Some(item), Some(EditorDrag::Move { offset })) => {
+                let position = grid_point + offset;
+                if edited.position(item) != Some(position) {
+                    edited.set_position(item, position);
+                    edited.saved = false;
+                }
+            }
+            (Some(EditorItem::Wall(index)), Some(EditorDrag::Reshape { handle, anchor })) => {
+                let Some(wall) = edited.definition.walls.get(index) else {
+                    return;
+                };
+                let reshaped = reshape_wall(wall, handle, anchor, grid_point);
+                if *wall != reshaped {
+                    edited.definition.walls[index] = reshaped;
+                    edited.saved = false;
+                }
+            }
+            _ => {}
+        }
+    } else if selection.drag.take().is_some() {
+        // A line keeps no point twice in a row, and no line is left without length
+        if let Some(EditorItem::Wall(index)) = selection.item {
+            let walls = &mut edited.definition.walls;
+            if let Some(WallShape::Polyline { points, .. }) = walls.get_mut(index) {
+                points.dedup();
+                if points.len() < 2 {
+                    walls.remove(index);
+                    selection.item = None;
+                }
+            }
+        }
+    }
+
+    if mouse.just_pressed(MouseButton::Right) && !over_panel {
+        if let Some(item) = edited.item_at(point) {
+            edited.remove(item);
+            edited.saved = false;
+            selection.item = None;
+            selection.drag = None;
+        }
+    }
+}
+
+/// Draws the grid and the edited level, the selection in yellow
+pub fn draw_editor(edited: Res<EditedLevel>, selection: Res<EditorSelection>, mut gizmos: Gizmos) {
+    let level = &edited.definition;
+    let half = level.arena / 2.0;
+    let grid = Color::srgba(1.0, 1.0, 1.0, 0.05);
+    let mut x = (-half.x / GRID_SIZE).ceil() * GRID_SIZE;
+    while x <= half.x {
+        gizmos.line_2d(Vec2::new(x, -half.y), Vec2::new(x, half.y), grid);
+        x += GRID_SIZE;
+    }
+    let mut y = (-half.y / GRID_SIZE).ceil() * GRID_SIZE;
+    while y <= half.y {
+        gizmos.line_2d(Vec2::new(-half.x, y), Vec2::new(half.x, y), grid);
+        y += GRID_SIZE;
+    }
+    gizmos.rect_2d(
+        Vec2::ZERO,
+        0.0,
+        level.arena + Vec2::splat(WALL_WIDTH * 2.0),
+        Color::linear_rgb(0.3, 0.3, 0.3),
+    );
+    gizmos.rect_2d(
+        Vec2::ZERO,
+        0.0,
+        level.arena,
+        Color::linear_rgb(0.3, 0.3, 0.3),
+    );
+
+    let selected = Color::from(tailwind::YELLOW_300);
+    let color = |item: EditorItem, color: Color| {
+        if selection.item == Some(item) {
+            selected
+        } else {
+            color
+        }
+    };
+
+    for (index, wall) in level.walls.iter().enumerate() {
+        let item = EditorItem::Wall(index);
+        for outline in wall.outlines() {
+            gizmos.linestrip_2d(
+                outline.iter().chain(outline.first()).copied(),
+                color(item, Color::linear_rgb(0.6, 0.6, 0.6)),
+            );
+        }
+        if selection.item == Some(item) {
+            for handle in wall_handles(wall) {
+                gizmos.circle_2d(handle, HANDLE_RADIUS, selected);
+            }
+        }
+    }
+    for (index, ennemy) in level.ennemies.iter().enumerate() {
+        let ennemy_color = color(EditorItem::Ennemy(index), Color::linear_rgb(0.6, 0.2, 0.1));
+        gizmos.circle_2d(ennemy.position, ennemy.kind.radius(), ennemy_color);
+        if ennemy.kind == EnnemyKind::Turret {
+            gizmos.circle_2d(ennemy.position, ennemy.kind.radius() / 2.0, ennemy_color);
+        }
+    }
+    for (index, objective) in level.objectives.iter().enumerate() {
+        gizmos.rect_2d(
+            *objective,
+            std::f32::consts::FRAC_PI_4,
+            Vec2::splat(OBJECTIVE_RADIUS * std::f32::consts::SQRT_2),
+            color(EditorItem::Objective(index), tailwind::AMBER_400.into()),
+        );
+    }
+    let spawn_color = color(EditorItem::Spawn, tailwind::LIME_400.into());
+    gizmos.circle_2d(level.spawn, PLAYER_RADIUS, spawn_color);
+    gizmos.line_2d(
+        level.spawn - Vec2::X * PLAYER_RADIUS,
+        level.spawn + Vec2::X * PLAYER_RADIUS,
+        spawn_color,
+    );
+    gizmos.line_2d(
+        level.spawn - Vec2::Y * PLAYER_RADIUS,
+        level.spawn + Vec2::Y * PLAYER_RADIUS,
+        spawn_color,
+    );
+}
+
+pub fn update_editor_text(
+    edited: Res<EditedLevel>,
+    tool: Res<EditorTool>,
+    mut text: Query<&mut Text, With<EditorText>>,
+) {
+    let state = if edited.saved { "Saved" } else { "Not saved" };
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!(
+            "{state}: {}\n\n{}\nLeft: place, select, move\nHandles: reshape\nRight/Delete: remove\nQ/E: turn a wall\nCtrl+S: save\nF6: playtest\nEscape: back",
+            edited.path,
+            tool.name(),
+        );
+    }
+}
+
+/// Where the files of the assets are, the game must run from the sources to save levels
+fn asset_file(path: &str) -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join(path)
+}
+
+fn write_level_file(edited: &EditedLevel) -> io::Result<()> {
+    let text =
+        ron::ser::to_string_pretty(&edited.definition, default()).map_err(io::Error::other)?;
+    std::fs::write(asset_file(&edited.path), text)
+}
+
+/// Adds the file of a new level at the end of the campaign
+fn add_to_campaign(path: &str) -> io::Result<()> {
+    let file = asset_file(CAMPAIGN_PATH);
+    let mut paths: Vec<String> =
+        ron::de::from_str(&std::fs::read_to_string(&file)?).map_err(io::Error::other)?;
+    if !paths.iter().any(|p| p == path) {
+        paths.push(path.to_string());
+    }
+    let text = ron::ser::to_string_pretty(&paths, default()).map_err(io::Error::other)?;
+    std::fs::write(file, text)
+}
+
+/// Writes the edited level to its file, and hands the new definition to the game
+///
+/// A level without ennemy is refused, it could not be won nor playtested
+pub fn save_edited_level(
+    mut save_event: EventReader<SaveLevelEvent>,
+    mut edited: ResMut<EditedLevel>,
+    asset_server: Res<AssetServer>,
+    mut definitions: ResMut<Assets<LevelDefinition>>,
+    mut levels: ResMut<Levels>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    if save_event.read().last().is_none() {
+        return;
+    }
+    if edited.definition.ennemies.is_empty() {
+        warn!(
+            "Level {} has no ennemy, place one before saving it",
+            edited.definition.id
+        );
+        return;
+    }
+    let result = write_level_file(&edited).and_then(|_| match edited.handle {
+        Some(_) => Ok(()),
+        None => add_to_campaign(&edited.path),
+    });
+    if let Err(e) = result {
+        error!("Could not save {}: {e}", edited.path);
+        return;
+    }
+
+    let edited = &mut *edited;
+    let definition = &edited.definition;
+    let handle = edited
+        .handle
+        .get_or_insert_with(|| asset_server.load(edited.path.clone()))
+        .clone();
+    definitions.insert(&handle, definition.clone());
+    match levels.iter_mut().find(|l| l.definition == handle) {
+        Some(level) => level.apply(definition),
+        None => {
+            levels.push(Level::new(definition, handle.clone()));
+            levels.sort_by_key(|l| l.id);
+        }
+    }
+    levels.unlock_available();
+
+    // A change of the current level would bring back its stored ghosts,
+    // so it is only changed by the first save of a new level
+    match current_level.bypass_change_detection().0.as_mut() {
+        Some(level) if level.definition == handle => level.apply(definition),
+        _ => current_level.0 = Some(levels.id(definition.id)),
+    }
+    edited.saved = true;
+    info!("Saved level {} to {}", definition.id, edited.path);
+}
+
+/// Plays the saved level with the selected class, or the first one the level allows
+///
+/// A level without ennemy is not played, [`end_playtest`] would end it at once
+pub fn start_playtest(
+    mut commands: Commands,
+    mut playtest_event: EventReader<PlaytestEvent>,
+    edited: Res<EditedLevel>,
+    mut selected_character: ResMut<SelectedCharacter>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if playtest_event.read().last().is_none() || !edited.saved {
+        return;
+    }
+    if edited.definition.ennemies.is_empty() {
+        warn!("Level {} has no ennemy to playtest", edited.definition.id);
+        return;
+    }
+    let characters = &edited.definition.characters;
+    if !characters.contains(&selected_character.0) {
+        let Some(class) = characters.first() else {
+            warn!("No class can play level {}", edited.definition.id);
+            return;
+        };
+        selected_character.set(*class);
+    }
+    commands.insert_resource(Playtest);
+    commands.insert_resource(UnscoredRun);
+    game_state.set(GameState::Play);
+}
+
+/// Goes back to the editor once the playtest is won or lost,
+/// it neither scores the level nor leaves a ghost
+pub fn end_playtest(
+    ennemies: Query<(), With<Ennemy>>,
+    mut save_player_ghost_event: EventReader<SavePlayerGhostEvent>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if save_player_ghost_event.read().last().is_some() || ennemies.is_empty() {
+        info!("Playtest over");
+        game_state.set(GameState::Editor);
+    }
+}
+
+pub fn back_to_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        game_state.set(GameState::Editor);
+    }
+}
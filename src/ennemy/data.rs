@@ -3,6 +3,7 @@ use std::time::Duration;
 use crate::player::prelude::*;
 use avian2d::prelude::*;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub enum AttackSpeedType {
     Regular,
@@ -19,7 +20,7 @@ pub struct Ennemy;
 /// - The knight have lot of health and does damage with melee attacks.
 /// - The ranger shoots from long range but with low damage.
 /// - The wizard inflicts high damages at medium range but is very weak.
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EnnemyKind {
     Dummy,
     Turret,
@@ -80,6 +81,7 @@ impl EnemyBundle {
     pub fn new(
         kind: EnnemyKind,
         radius: f32,
+        position: Vec2,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
     ) -> Self {
@@ -91,7 +93,7 @@ impl EnemyBundle {
             mesh: ColorMesh2dBundle {
                 mesh: meshes.add(Circle::new(radius)).into(),
                 material: materials.add(Color::linear_rgb(0.6, 0.2, 0.1)),
-                transform: Transform::from_xyz(300.0, 100.0, 0.0),
+                transform: Transform::from_translation(position.extend(0.0)),
                 ..default()
             },
             rigid_body: RigidBody::Static,
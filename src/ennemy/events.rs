@@ -0,0 +1,15 @@
+use bevy::prelude::*;
+
+use super::data::EnnemyKind;
+
+/// Sent when a projectile kills an [`Ennemy`](super::data::Ennemy)
+///
+/// The ennemy is already despawned when the event is read,
+/// so its kind is kept in the event
+#[derive(Event, Debug, Clone)]
+pub struct EnnemyKilledEvent {
+    pub ennemy: Entity,
+    pub kind: EnnemyKind,
+    /// The entity that shot the projectile
+    pub killer: Entity,
+}
@@ -1,25 +1,31 @@
 mod data;
+mod events;
 mod systems;
 
 use bevy::prelude::*;
+use events::*;
 use systems::*;
 
-use crate::game::GameState;
+use crate::game::{GameState, LevelSetup};
 
 pub mod prelude {
     pub use super::data::*;
+    pub use super::events::*;
     pub use super::EnnemyPlugin;
-    use crate::player::prelude::*;
 }
 
 pub struct EnnemyPlugin;
 
 impl Plugin for EnnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Play), spawn_ennemies)
+        app.add_event::<EnnemyKilledEvent>()
+            .add_systems(LevelSetup, spawn_ennemies)
+            // Ennemies act on the fixed clock so a replayed cycle is rebuilt identically
             .add_systems(
-                Update,
-                (tick_attack_speed, execute_always_attack).run_if(in_state(GameState::Play)),
+                FixedUpdate,
+                (tick_attack_speed, execute_always_attack)
+                    .chain()
+                    .run_if(in_state(GameState::Play)),
             );
     }
 }
@@ -1,46 +1,51 @@
 use crate::{
-    game::{CurrentLevel, GameState},
+    game::{CurrentLevel, GameState, LevelBody},
     AttackProjectile, Ghost, Player,
 };
 
 use super::prelude::*;
+use crate::levels::prelude::*;
 use crate::player::prelude::*;
 use avian2d::prelude::*;
 use bevy::{color::palettes::tailwind, prelude::*};
 
 /// Spawn ennemy to the map.
 ///
-/// For now the ennemy is spawned to the right of the level.
+/// The ennemies are placed by the definition of the current level.
 pub fn spawn_ennemies(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     current_level: Res<CurrentLevel>,
+    definitions: Res<Assets<LevelDefinition>>,
 ) {
-    let Some(level) = &current_level.0 else {
+    let Some(level) = current_level.definition(&definitions) else {
         return;
     };
 
-    match level.id {
-        1 => {
-            let kind = EnnemyKind::Dummy;
-            let radius = kind.radius();
-            commands.spawn((
-                StateScoped(GameState::Play),
-                EnemyBundle::new(kind, radius, &mut meshes, &mut materials),
-            ));
-        }
-        2 => {
-            let kind = EnnemyKind::Turret;
-            let radius = kind.radius();
-            commands.spawn((
-                StateScoped(GameState::Play),
-                AlwaysAttack,
-                AttackSpeed::from_type(AttackSpeedType::Regular),
-                EnemyBundle::new(kind, radius, &mut meshes, &mut materials),
-            ));
+    for placement in level.ennemies.iter() {
+        let kind = placement.kind;
+        let radius = kind.radius();
+        let mut ennemy = commands.spawn((
+            StateScoped(GameState::Play),
+            LevelBody,
+            EnemyBundle::new(
+                kind,
+                radius,
+                placement.position,
+                &mut meshes,
+                &mut materials,
+            ),
+        ));
+        match kind {
+            EnnemyKind::Dummy => {}
+            EnnemyKind::Turret => {
+                ennemy.insert((
+                    AlwaysAttack,
+                    AttackSpeed::from_type(AttackSpeedType::Regular),
+                ));
+            }
         }
-        _ => {}
     }
 }
 
@@ -52,13 +57,16 @@ pub fn tick_attack_speed(time: Res<Time>, mut query: Query<&mut AttackSpeed, Wit
 }
 
 pub fn execute_always_attack(
-    mut ennemy_query: Query<(&mut AttackSpeed, &Transform), (With<Ennemy>, With<AlwaysAttack>)>,
+    mut ennemy_query: Query<
+        (Entity, &mut AttackSpeed, &Transform),
+        (With<Ennemy>, With<AlwaysAttack>),
+    >,
     player_query: Query<&Transform, Or<(With<Ghost>, With<Player>)>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for (mut attack_seed, ennemy_tranform) in ennemy_query.iter_mut() {
+    for (ennemy, mut attack_seed, ennemy_tranform) in ennemy_query.iter_mut() {
         if !attack_seed.finished() {
             continue;
         }
@@ -86,8 +94,9 @@ pub fn execute_always_attack(
             // attack
             commands.spawn((
                 StateScoped(GameState::Play),
+                LevelBody,
                 Team::Enemy,
-                AttackProjectile::new(projectile_transform.translation.truncate(), 3000.0),
+                AttackProjectile::new(projectile_transform.translation.truncate(), 3000.0, ennemy),
                 ColorMesh2dBundle {
                     mesh: meshes.add(Circle::new(7.0)).into(),
                     material: materials.add(Color::from(tailwind::PINK_400)),
@@ -1,20 +1,75 @@
+use crate::levels::prelude::*;
 use crate::Class;
+use bevy::ecs::schedule::ScheduleLabel;
 use bevy::prelude::*;
 
 /// seperate the different phases of the game
 #[derive(States, Clone, PartialEq, Eq, Hash, Debug, Default)]
 pub enum GameState {
+    /// Waiting for the level assets
     #[default]
+    Loading,
     LevelSelection,
     CharacterSelection,
     Play,
     GameOver,
+    /// Watching a recorded cycle, no simulation runs
+    Watch,
+    /// Editing the definition of a level
+    Editor,
 }
 
+/// Whether the cycle being played runs, it only exists during [`GameState::Play`]
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[source(GameState = GameState::Play)]
+pub enum PlayState {
+    #[default]
+    Running,
+    /// The clocks are stopped, no tick is simulated nor recorded
+    Paused,
+}
+
+/// Spawns the bodies of the current level and rewinds its simulation to the first tick
+///
+/// Runs when a cycle starts and again when the timeline seeks in it,
+/// so the menus and the recorders of the cycle are not set up here
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LevelSetup;
+
+/// Marks what [`LevelSetup`] spawns and what the simulation spawns from it,
+/// they are despawned before the level is set up again
+#[derive(Component)]
+pub struct LevelBody;
+
 /// holds the current level if there is one
 #[derive(Resource, Default)]
 pub struct CurrentLevel(pub Option<Level>);
 
+impl CurrentLevel {
+    /// The asset describing the current level, once it is loaded
+    pub fn definition<'a>(
+        &self,
+        definitions: &'a Assets<LevelDefinition>,
+    ) -> Option<&'a LevelDefinition> {
+        self.0
+            .as_ref()
+            .and_then(|level| definitions.get(&level.definition))
+    }
+}
+
+/// Present while the run being played is only a trial,
+/// it neither scores the level nor becomes a ghost
+#[derive(Resource, Debug)]
+pub struct UnscoredRun;
+
+/// Sent when every ennemy of the current level is dead
+#[derive(Event, Debug, Clone)]
+pub struct LevelCompletedEvent {
+    pub level: usize,
+    /// the score recorded for this completion
+    pub cycles: usize,
+}
+
 #[derive(Clone)]
 pub struct Level {
     pub id: usize,
@@ -24,10 +79,32 @@ pub struct Level {
     pub cycles: Option<usize>,
     /// available characters,
     pub characters: Vec<Class>,
+    /// what unlocks this level
+    pub requires: Vec<Prerequisite>,
+    pub definition: Handle<LevelDefinition>,
 }
 
-/// holds the current level if there is one
-#[derive(Resource, Deref, DerefMut)]
+impl Level {
+    pub fn new(definition: &LevelDefinition, handle: Handle<LevelDefinition>) -> Self {
+        Level {
+            id: definition.id,
+            unlocked: definition.requires.is_empty(),
+            cycles: None,
+            characters: definition.characters.clone(),
+            requires: definition.requires.clone(),
+            definition: handle,
+        }
+    }
+
+    /// Takes the changes of its definition, the score is kept
+    pub fn apply(&mut self, definition: &LevelDefinition) {
+        self.characters = definition.characters.clone();
+        self.requires = definition.requires.clone();
+    }
+}
+
+/// holds the levels of the campaign, filled once their assets are loaded
+#[derive(Resource, Deref, DerefMut, Default)]
 pub struct Levels(pub Vec<Level>);
 impl Levels {
     pub fn id(&self, id: usize) -> Level {
@@ -45,31 +122,36 @@ impl Levels {
             .expect("Could not get the right level from Levels")
     }
 
+    /// Records the score of a level and unlocks the levels it opens
     pub fn set_next_score(&mut self, id: usize, cycles: usize) {
         self.id_mut(id).cycles = Some(cycles);
+        self.unlock_available();
     }
 
     pub fn unlock_level(&mut self, id: usize) {
         self.id_mut(id).unlocked = true;
     }
-}
 
-impl Default for Levels {
-    fn default() -> Self {
-        Self(vec![
-            Level {
-                id: 1,
-                unlocked: true,
-                cycles: None,
-                characters: vec![Class::Knight, Class::Ranger, Class::Wizard], // Temporarly all classes for test purposes
-            },
-            Level {
-                id: 2,
-                unlocked: true,
-                cycles: None,
-                characters: vec![Class::Knight, Class::Wizard],
-            },
-        ])
+    /// Unlocks the levels whose prerequisites are all met, an unlocked level stays unlocked
+    pub fn unlock_available(&mut self) {
+        let available = self
+            .0
+            .iter()
+            .filter(|l| !l.unlocked && self.missing(l).is_empty())
+            .map(|l| l.id)
+            .collect::<Vec<_>>();
+        for id in available {
+            self.unlock_level(id);
+        }
+    }
+
+    /// The prerequisites of a level that are not met yet
+    pub fn missing<'a>(&self, level: &'a Level) -> Vec<&'a Prerequisite> {
+        level
+            .requires
+            .iter()
+            .filter(|prerequisite| !prerequisite.is_met(&self.0))
+            .collect()
     }
 }
 
@@ -77,18 +159,31 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<GameState>()
+            .add_event::<LevelCompletedEvent>()
             .enable_state_scoped_entities::<GameState>()
+            .add_sub_state::<PlayState>()
+            .enable_state_scoped_entities::<PlayState>()
             .init_resource::<CurrentLevel>()
             .init_resource::<Levels>()
+            .init_schedule(LevelSetup)
+            .add_systems(OnEnter(GameState::Play), setup_level)
             .add_systems(
                 Update,
-                debug_game_over.run_if(|keyboard_input: Res<ButtonInput<KeyCode>>| {
-                    keyboard_input.just_pressed(KeyCode::KeyK)
-                }),
+                debug_game_over
+                    // Not gated on running, the game over screen has no play state
+                    .run_if(not(in_state(PlayState::Paused)))
+                    .run_if(|keyboard_input: Res<ButtonInput<KeyCode>>| {
+                        keyboard_input.just_pressed(KeyCode::KeyK)
+                    }),
             );
     }
 }
 
+/// Runs [`LevelSetup`], the entities it spawns exist once it returns
+pub fn setup_level(world: &mut World) {
+    world.run_schedule(LevelSetup);
+}
+
 fn debug_game_over(state: Res<State<GameState>>, mut next_state: ResMut<NextState<GameState>>) {
     if state.get() == &GameState::GameOver {
         next_state.set(GameState::LevelSelection);
@@ -96,3 +191,93 @@ fn debug_game_over(state: Res<State<GameState>>, mut next_state: ResMut<NextStat
         next_state.set(GameState::GameOver);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(id: usize, requires: Vec<Prerequisite>) -> Level {
+        Level {
+            id,
+            unlocked: requires.is_empty(),
+            cycles: None,
+            characters: vec![Class::Knight],
+            requires,
+            definition: Handle::default(),
+        }
+    }
+
+    fn unlocked(levels: &Levels) -> Vec<usize> {
+        levels.iter().filter(|l| l.unlocked).map(|l| l.id).collect()
+    }
+
+    #[test]
+    fn completed_levels_unlock_the_next_ones() {
+        let mut levels = Levels(vec![
+            level(1, vec![]),
+            level(2, vec![Prerequisite::Completed(1)]),
+            level(3, vec![Prerequisite::Completed(2)]),
+        ]);
+        levels.unlock_available();
+        assert_eq!(unlocked(&levels), vec![1]);
+
+        levels.set_next_score(1, 4);
+        assert_eq!(unlocked(&levels), vec![1, 2]);
+        levels.set_next_score(2, 7);
+        assert_eq!(unlocked(&levels), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn cycles_unlock_under_their_threshold() {
+        let mut levels = Levels(vec![
+            level(1, vec![]),
+            level(
+                2,
+                vec![Prerequisite::Cycles {
+                    level: 1,
+                    at_most: 2,
+                }],
+            ),
+        ]);
+        levels.set_next_score(1, 3);
+        assert_eq!(unlocked(&levels), vec![1]);
+        assert_eq!(levels.missing(&levels.id(2)).len(), 1);
+
+        levels.set_next_score(1, 2);
+        assert_eq!(unlocked(&levels), vec![1, 2]);
+
+        // A worse score replaces the better one, the level it unlocked stays unlocked
+        levels.set_next_score(1, 5);
+        assert_eq!(levels.id(1).cycles, Some(5));
+        assert_eq!(levels.missing(&levels.id(2)).len(), 1);
+        assert_eq!(unlocked(&levels), vec![1, 2]);
+    }
+
+    #[test]
+    fn unmet_prerequisites_keep_a_level_locked() {
+        let mut levels = Levels(vec![
+            level(1, vec![]),
+            level(2, vec![]),
+            level(
+                3,
+                vec![
+                    Prerequisite::Completed(1),
+                    Prerequisite::Cycles {
+                        level: 2,
+                        at_most: 1,
+                    },
+                ],
+            ),
+        ]);
+        levels.set_next_score(1, 1);
+        levels.set_next_score(2, 2);
+        assert_eq!(unlocked(&levels), vec![1, 2]);
+        assert_eq!(
+            levels.missing(&levels.id(3)),
+            vec![&Prerequisite::Cycles {
+                level: 2,
+                at_most: 1
+            }]
+        );
+    }
+}
@@ -1,9 +1,129 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use super::events::GhostDivergedEvent;
+use super::replay::*;
+use super::table::*;
 use crate::player::prelude::*;
+use bevy::ecs::component::Tick;
 use bevy::prelude::*;
 
+/// The recorded events of one event type
+///
+/// The records of each ghost are encoded one after the other in tick order,
+/// as the ticks since the previous record followed by the event.
+/// Each ghost has a replay cursor pointing to its first record
+/// that has not been replayed yet
 #[derive(Resource)]
 pub struct LevelHistory<T: Event> {
-    pub events: Vec<EventRecord<T>>,
+    ghosts: Vec<GhostRecords>,
+    revision: u64,
+    event: PhantomData<fn() -> T>,
+}
+
+/// The encoded records of one ghost
+#[derive(Default, Clone)]
+struct GhostRecords {
+    bytes: Vec<u8>,
+    len: usize,
+    /// Tick of the last record
+    last_tick: u64,
+    /// Where the event of the last record starts
+    last_event: usize,
+    /// Where the first record that has not been replayed starts
+    cursor: usize,
+    /// Tick of the record before the cursor
+    cursor_tick: u64,
+}
+
+/// Where a record is in the bytes of its ghost
+struct RecordSpan {
+    start: usize,
+    event: usize,
+    end: usize,
+    tick: u64,
+}
+
+/// Reads the record at `offset`, whose tick follows `tick`, and moves both past it
+fn read_record<E: ReplayCodec>(bytes: &[u8], offset: &mut usize, tick: &mut u64) -> E {
+    let mut reader = ReplayReader(&bytes[*offset..]);
+    let event = reader
+        .varint()
+        .and_then(|delta| {
+            *tick += delta;
+            E::decode(&mut reader)
+        })
+        .expect("records are read back as they were written");
+    *offset = bytes.len() - reader.0.len();
+    event
+}
+
+impl GhostRecords {
+    /// Appends a record, its tick is not before the tick of the last record
+    fn push<E: ReplayCodec>(&mut self, tick: u64, event: &E) {
+        let mut writer = ReplayWriter(std::mem::take(&mut self.bytes));
+        writer.varint(tick - self.last_tick);
+        self.last_event = writer.0.len();
+        event.encode(&mut writer);
+        self.bytes = writer.0;
+        self.len += 1;
+        self.last_tick = tick;
+    }
+
+    /// Decodes every record, in order
+    fn records<E: ReplayCodec>(&self) -> impl Iterator<Item = (u64, E)> + '_ {
+        let mut offset = 0;
+        let mut tick = 0;
+        std::iter::from_fn(move || {
+            (offset < self.bytes.len()).then(|| {
+                let event = read_record(&self.bytes, &mut offset, &mut tick);
+                (tick, event)
+            })
+        })
+    }
+
+    /// Where each record is, in order
+    fn spans<E: ReplayCodec>(&self) -> Vec<RecordSpan> {
+        let mut spans = vec![];
+        let (mut offset, mut tick) = (0, 0);
+        while offset < self.bytes.len() {
+            let start = offset;
+            let mut reader = ReplayReader(&self.bytes[offset..]);
+            reader
+                .varint()
+                .expect("records are read back as they were written");
+            let event = self.bytes.len() - reader.0.len();
+            read_record::<E>(&self.bytes, &mut offset, &mut tick);
+            spans.push(RecordSpan {
+                start,
+                event,
+                end: offset,
+                tick,
+            });
+        }
+        spans
+    }
+
+    /// The tick of the record at the cursor, if there is one
+    fn next_tick(&self) -> Option<u64> {
+        (self.cursor < self.bytes.len()).then(|| {
+            let delta = ReplayReader(&self.bytes[self.cursor..])
+                .varint()
+                .expect("records are read back as they were written");
+            self.cursor_tick + delta
+        })
+    }
+
+    /// Reads the record at the cursor and moves the cursor past it
+    fn replay<E: ReplayCodec>(&mut self) -> E {
+        read_record(&self.bytes, &mut self.cursor, &mut self.cursor_tick)
+    }
+
+    fn rewind(&mut self) {
+        self.cursor = 0;
+        self.cursor_tick = 0;
+    }
 }
 
 /// An indicator component for a player ghost
@@ -13,32 +133,349 @@ pub struct Ghost;
 pub struct PlayerGhost {
     pub entity: Option<Entity>,
     pub class: Class,
+    /// A muted ghost keeps its records but is neither spawned nor replayed
+    pub muted: bool,
 }
 
 #[derive(Resource, Default)]
 pub struct PlayerGhostList {
     pub ghosts: Vec<PlayerGhost>,
+    /// The ghost the live player took over for the current cycle, if any
+    pub forked: Option<GhostIdentifier>,
+    /// The tick the ghost was taken over at, a restart of the cycle resumes from it
+    pub forked_at: u64,
 }
 
 impl PlayerGhostList {
     pub fn get_ghost(&self, identifier: GhostIdentifier) -> Option<&PlayerGhost> {
         self.ghosts.get(identifier.0)
     }
+
+    /// The identifier the live player records under,
+    /// the ghost it took over or the one after the last ghost
+    pub fn live(&self) -> GhostIdentifier {
+        self.forked.unwrap_or(GhostIdentifier(self.ghosts.len()))
+    }
+
+    /// Whether the ghost is replayed as its own entity during the cycle
+    pub fn is_spawned(&self, identifier: GhostIdentifier) -> bool {
+        self.get_ghost(identifier).is_some_and(|g| !g.muted) && self.forked != Some(identifier)
+    }
 }
 
 impl<E: Event> Default for LevelHistory<E> {
     fn default() -> Self {
-        Self { events: vec![] }
+        Self {
+            ghosts: vec![],
+            revision: 0,
+            event: PhantomData,
+        }
     }
 }
 
-#[derive(Resource, Default)]
-pub struct LevelStartTime(pub f64);
+impl<E: Event> LevelHistory<E> {
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.ghosts.iter().map(|g| g.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The tick of the last record of a ghost, if it has one
+    pub fn last_tick(&self, ghost: GhostIdentifier) -> Option<u64> {
+        self.ghosts
+            .get(ghost.0)
+            .filter(|g| g.len > 0)
+            .map(|g| g.last_tick)
+    }
+
+    /// Changes whenever records are removed, views of the history rebuild when it does
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Memory held by the records, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.ghosts.capacity() * std::mem::size_of::<GhostRecords>()
+            + self
+                .ghosts
+                .iter()
+                .map(|g| g.bytes.capacity())
+                .sum::<usize>()
+    }
+
+    /// Moves the cursors back to the start of the level
+    pub fn rewind(&mut self) {
+        for records in self.ghosts.iter_mut() {
+            records.rewind();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ghosts.clear();
+        self.revision += 1;
+    }
+}
+
+impl<E: Event + ReplayCodec> LevelHistory<E> {
+    /// All recorded events, sorted by tick
+    pub fn events(&self) -> Vec<EventRecord<E>> {
+        let mut events = self
+            .ghosts
+            .iter()
+            .enumerate()
+            .flat_map(|(ghost, records)| {
+                records.records().map(move |(tick, event)| EventRecord {
+                    ghost: GhostIdentifier(ghost),
+                    tick,
+                    event,
+                })
+            })
+            .collect::<Vec<_>>();
+        events.sort_by_key(|r| r.tick);
+        events
+    }
+
+    /// The records of a ghost from `tick` onward
+    pub fn ghost_events_from(&self, ghost: GhostIdentifier, tick: u64) -> Vec<EventRecord<E>> {
+        self.ghosts
+            .get(ghost.0)
+            .into_iter()
+            .flat_map(|records| records.records())
+            .filter(|(t, _)| *t >= tick)
+            .map(|(tick, event)| EventRecord { ghost, tick, event })
+            .collect()
+    }
+
+    /// Gives the records their new ghost, records mapped to `None` are dropped
+    ///
+    /// Used when ghosts are deleted or reordered, the records of the live player
+    /// must be mapped too as its identifier follows the last ghost.
+    /// The ghosts named by the events are mapped as well
+    pub fn remap_ghosts(&mut self, map: &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>) {
+        let mut ghosts = vec![];
+        for (index, mut records) in std::mem::take(&mut self.ghosts).into_iter().enumerate() {
+            if let Some(ghost) = map(GhostIdentifier(index)) {
+                if E::NAMES_GHOSTS {
+                    let mut remapped = GhostRecords::default();
+                    for (tick, mut event) in records.records::<E>() {
+                        if event.remap_ghosts(map) {
+                            remapped.push(tick, &event);
+                        }
+                    }
+                    records = remapped;
+                }
+                if ghosts.len() <= ghost.0 {
+                    ghosts.resize_with(ghost.0 + 1, GhostRecords::default);
+                }
+                ghosts[ghost.0] = records;
+            }
+        }
+        self.ghosts = ghosts;
+        self.rewind();
+        self.revision += 1;
+    }
+
+    /// Stores a record after all the records of its ghost at the same tick
+    ///
+    /// Records are expected in tick order, an earlier record
+    /// has the records of its ghost encoded again
+    pub fn push(&mut self, record: EventRecord<E>) {
+        let ghost = record.ghost.0;
+        if self.ghosts.len() <= ghost {
+            self.ghosts.resize_with(ghost + 1, GhostRecords::default);
+        }
+        let records = &mut self.ghosts[ghost];
+        if records.len == 0 || record.tick >= records.last_tick {
+            records.push(record.tick, &record.event);
+            return;
+        }
+
+        let replayed = records
+            .spans::<E>()
+            .iter()
+            .take_while(|span| span.end <= records.cursor)
+            .count();
+        let mut events = records.records::<E>().collect::<Vec<_>>();
+        let index = events.partition_point(|(tick, _)| *tick <= record.tick);
+        events.insert(index, (record.tick, record.event));
+        let replayed = if index < replayed {
+            replayed + 1
+        } else {
+            replayed
+        };
+
+        let mut encoded = GhostRecords::default();
+        for (tick, event) in events.iter() {
+            encoded.push(*tick, event);
+        }
+        for _ in 0..replayed {
+            encoded.replay::<E>();
+        }
+        *records = encoded;
+    }
+
+    /// Returns the records of `tick` and moves the cursors past them
+    ///
+    /// Records of earlier ticks that were not replayed are skipped,
+    /// so each call only reads the records that are due
+    pub fn due(&mut self, tick: u64) -> Vec<EventRecord<E>> {
+        self.due_for(tick, |_| true)
+    }
+
+    /// Returns the records of `tick` of the ghosts that are `replayed`
+    ///
+    /// The records of the other ghosts are not read, their cursors stay
+    /// where they are until the history is rewound
+    pub fn due_for(
+        &mut self,
+        tick: u64,
+        replayed: impl Fn(GhostIdentifier) -> bool,
+    ) -> Vec<EventRecord<E>> {
+        let mut due = vec![];
+        for (ghost, records) in self.ghosts.iter_mut().enumerate() {
+            if !replayed(GhostIdentifier(ghost)) {
+                continue;
+            }
+            while let Some(next) = records.next_tick().filter(|next| *next <= tick) {
+                let event = records.replay();
+                if next == tick {
+                    due.push(EventRecord {
+                        ghost: GhostIdentifier(ghost),
+                        tick,
+                        event,
+                    });
+                }
+            }
+        }
+        due
+    }
+
+    /// Stores a record unless it repeats the last record of its ghost,
+    /// returns whether it was stored
+    ///
+    /// Records are compared by their encoding, so only the changes of
+    /// a [`ReplayCodec::HELD`] event type make it to the history
+    pub fn push_change(&mut self, record: EventRecord<E>) -> bool {
+        let Some(records) = self
+            .ghosts
+            .get_mut(record.ghost.0)
+            .filter(|r| r.len > 0 && record.tick >= r.last_tick)
+        else {
+            self.push(record);
+            return true;
+        };
+        let (len, last_tick, last_event) =
+            (records.bytes.len(), records.last_tick, records.last_event);
+        records.push(record.tick, &record.event);
+        if records.bytes[last_event..len] != records.bytes[records.last_event..] {
+            return true;
+        }
+        records.bytes.truncate(len);
+        records.len -= 1;
+        records.last_tick = last_tick;
+        records.last_event = last_event;
+        false
+    }
+
+    /// Forgets the records of a ghost from `tick` onward and rewinds the history
+    pub fn discard_from(&mut self, ghost: GhostIdentifier, tick: u64) {
+        if let Some(records) = self.ghosts.get_mut(ghost.0) {
+            let spans = records.spans::<E>();
+            let kept = spans.partition_point(|span| span.tick < tick);
+            if let Some(first) = spans.get(kept) {
+                records.bytes.truncate(first.start);
+                records.len = kept;
+                (records.last_tick, records.last_event) = match kept.checked_sub(1) {
+                    Some(last) => (spans[last].tick, spans[last].event),
+                    None => (0, 0),
+                };
+            }
+        }
+        self.rewind();
+        self.revision += 1;
+    }
+}
+
+/// Rate of the fixed simulation clock, shared by the physics and the level history
+pub const TICK_RATE: f64 = 144.0;
+
+/// Number of ticks between two position keyframes of a ghost
+pub const KEYFRAME_INTERVAL: u64 = 36;
+
+/// How replayed ghosts are brought back to their recorded keyframes
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub enum KeyframeCorrection {
+    /// Teleports the ghost onto the keyframe
+    Snap,
+    /// Spreads the position error over the given number of ticks,
+    /// the rotation is always snapped
+    Smooth { ticks: u32 },
+}
+
+impl Default for KeyframeCorrection {
+    fn default() -> Self {
+        KeyframeCorrection::Smooth { ticks: 12 }
+    }
+}
+
+/// The part of a keyframe correction that is still to be applied to a ghost
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DriftCorrection {
+    pub offset: Vec2,
+    pub ticks_left: u32,
+}
+
+/// Distance from its recorded keyframe past which a ghost is considered off-script, in pixels
+pub const DIVERGENCE_DISTANCE: f32 = 16.0;
+
+/// Recorded events that tell where their ghost was
+pub trait KeyframeEvent {
+    fn entity(&self) -> Entity;
+    fn position(&self) -> Vec2;
+}
+
+/// The first divergence of each ghost during the current cycle
+#[derive(Resource, Default, Debug)]
+pub struct GhostDivergences(pub Vec<GhostDivergedEvent>);
+
+/// A ghost killed during the current cycle
+#[derive(Debug, Clone)]
+pub struct GhostCasualty {
+    pub ghost: GhostIdentifier,
+    pub tick: u64,
+    pub killer: Killer,
+    pub position: Vec2,
+}
+
+/// The ghosts killed during the last cycle, kept until the next one starts
+#[derive(Resource, Default, Debug)]
+pub struct GhostCasualties(pub Vec<GhostCasualty>);
+
+/// The tick of the last record of each ghost, measured when they spawn
+#[derive(Resource, Default, Debug)]
+pub struct GhostRecordingEnds(pub Vec<u64>);
+
+/// The number of fixed simulation ticks since the level started
+///
+/// Events are recorded and replayed against this counter, so a replay
+/// does not depend on the frame rate it is played at
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelTick(pub u64);
+
+impl LevelTick {
+    /// Seconds of simulation time elapsed at this tick
+    pub fn as_secs(&self) -> f64 {
+        self.0 as f64 / TICK_RATE
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct EventRecord<E: Event> {
     pub ghost: GhostIdentifier,
-    pub timestamp: f64,
+    pub tick: u64,
     pub event: E,
 }
 
@@ -59,8 +496,634 @@ pub trait EventSourceMethods {
 
 pub trait EventRecordDebug {
     fn get_debug_color(&self, identifier: GhostIdentifier) -> Color;
+    /// Size of the marker of the event on the timeline, in pixels
+    fn get_debug_size(&self) -> Vec2 {
+        Vec2::new(1.0, 10.0)
+    }
+}
+
+/// How much the recorded histories hold
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryUsage {
+    pub records: usize,
+    /// Memory held by the records, in bytes
+    pub bytes: usize,
+}
+
+impl fmt::Display for HistoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} records in {:.1} KiB",
+            self.records,
+            self.bytes as f64 / 1024.0
+        )
+    }
 }
 
 /// Ghost index identifier
 #[derive(Component, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct GhostIdentifier(pub usize);
+
+/// A history read from a file, calling it inserts the history
+/// and returns the tick of its last record
+type DecodedHistory = Box<dyn FnOnce(&mut World) -> Option<u64>>;
+
+/// The operations on the history of one recorded event type
+/// that do not know the type
+#[derive(Clone, Copy)]
+struct RecordedEventType {
+    tag: [u8; 4],
+    write_history: fn(&mut ReplayFile, &World),
+    decode_history: fn(&ReplayFile) -> Result<DecodedHistory, ReplayError>,
+    discard_from: fn(&mut World, GhostIdentifier, u64),
+    remap_ghosts: fn(&mut World, &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>),
+    /// Raises the entry of each ghost to the tick of its last record
+    last_ticks: fn(&World, &mut [u64]),
+    usage: fn(&World) -> HistoryUsage,
+    ghost_records: fn(&World, GhostIdentifier) -> GhostRecords,
+    restore_ghost: fn(&mut World, GhostIdentifier, GhostRecords),
+    state: fn(&World) -> HistoryState,
+    markers: fn(&World, Option<(GhostIdentifier, u64)>, &mut Vec<RecordMarker>),
+    kind: &'static str,
+    fields: &'static [&'static str],
+    to_rows: fn(&ReplayFile, &mut Vec<RecordRow>) -> Result<(), ReplayError>,
+    from_rows: fn(&mut ReplayFile, &[RecordRow]),
+}
+
+fn write_history<E: Event + ReplayCodec>(file: &mut ReplayFile, world: &World) {
+    file.write_history(world.resource::<LevelHistory<E>>());
+}
+
+fn decode_history<E: Event + ReplayCodec>(
+    file: &ReplayFile,
+) -> Result<DecodedHistory, ReplayError> {
+    let history = file.read_history::<E>()?;
+    Ok(Box::new(move |world: &mut World| {
+        let last_tick = (0..history.ghosts.len())
+            .filter_map(|ghost| history.last_tick(GhostIdentifier(ghost)))
+            .max();
+        world.insert_resource(history);
+        last_tick
+    }))
+}
+
+fn discard_from<E: Event + ReplayCodec>(world: &mut World, ghost: GhostIdentifier, tick: u64) {
+    world
+        .resource_mut::<LevelHistory<E>>()
+        .discard_from(ghost, tick);
+}
+
+fn remap_ghosts<E: Event + ReplayCodec>(
+    world: &mut World,
+    map: &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>,
+) {
+    world.resource_mut::<LevelHistory<E>>().remap_ghosts(map);
+}
+
+fn last_ticks<E: Event>(world: &World, ticks: &mut [u64]) {
+    let history = world.resource::<LevelHistory<E>>();
+    for (ghost, tick) in ticks.iter_mut().enumerate() {
+        if let Some(last) = history.last_tick(GhostIdentifier(ghost)) {
+            *tick = (*tick).max(last);
+        }
+    }
+}
+
+fn usage<E: Event>(world: &World) -> HistoryUsage {
+    let history = world.resource::<LevelHistory<E>>();
+    HistoryUsage {
+        records: history.len(),
+        bytes: history.memory_usage(),
+    }
+}
+
+fn ghost_records<E: Event>(world: &World, ghost: GhostIdentifier) -> GhostRecords {
+    let history = world.resource::<LevelHistory<E>>();
+    history.ghosts.get(ghost.0).cloned().unwrap_or_default()
+}
+
+fn restore_ghost<E: Event>(world: &mut World, ghost: GhostIdentifier, records: GhostRecords) {
+    let mut history = world.resource_mut::<LevelHistory<E>>();
+    if history.ghosts.len() <= ghost.0 {
+        history
+            .ghosts
+            .resize_with(ghost.0 + 1, GhostRecords::default);
+    }
+    history.ghosts[ghost.0] = records;
+    history.rewind();
+    history.revision += 1;
+}
+
+/// The records of one ghost in every history
+pub struct GhostStash {
+    ghost: GhostIdentifier,
+    records: Vec<GhostRecords>,
+}
+
+/// The records of the ghost the live player took over, as they were before
+///
+/// They are put back when the cycle is abandoned, and forgotten once the run
+/// of the live player replaces them by ending as a ghost or completing the level
+#[derive(Resource, Default)]
+pub struct ForkedRecords(pub Option<GhostStash>);
+
+fn state<E: Event>(world: &World) -> HistoryState {
+    let history = world.resource::<LevelHistory<E>>();
+    HistoryState {
+        added: world
+            .get_resource_change_ticks::<LevelHistory<E>>()
+            .map(|ticks| ticks.added_tick())
+            .unwrap_or_default(),
+        revision: history.revision(),
+        len: history.len(),
+    }
+}
+
+/// Where a history is at, to tell which of its records are new
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryState {
+    /// When the history was inserted, a history read from a file is a new one
+    pub added: Tick,
+    /// Changes when records are removed, see [`LevelHistory::revision`]
+    pub revision: u64,
+    pub len: usize,
+}
+
+fn markers<E: Event + ReplayCodec + EventRecordDebug>(
+    world: &World,
+    from: Option<(GhostIdentifier, u64)>,
+    markers: &mut Vec<RecordMarker>,
+) {
+    let history = world.resource::<LevelHistory<E>>();
+    let records = match from {
+        Some((ghost, tick)) => history.ghost_events_from(ghost, tick),
+        None => history.events(),
+    };
+    markers.extend(records.into_iter().map(|record| RecordMarker {
+        ghost: record.ghost,
+        tick: record.tick,
+        color: record.event.get_debug_color(record.ghost),
+        size: record.event.get_debug_size(),
+    }));
+}
+
+/// How a record is shown on the timeline
+#[derive(Debug, Clone, Copy)]
+pub struct RecordMarker {
+    pub ghost: GhostIdentifier,
+    pub tick: u64,
+    pub color: Color,
+    pub size: Vec2,
+}
+
+/// Every event type registered with `add_recorded_event`
+///
+/// Lets all the histories be handled at once without naming each event type
+#[derive(Resource, Default, Clone)]
+pub struct RecordedEvents {
+    types: Vec<RecordedEventType>,
+}
+
+impl RecordedEvents {
+    /// Whether the event type is registered
+    pub fn contains<E: ReplayCodec>(&self) -> bool {
+        self.types.iter().any(|t| t.tag == E::TAG)
+    }
+
+    pub fn register<E: Event + ReplayCodec + RecordFields + EventRecordDebug>(&mut self) {
+        assert_ne!(
+            E::KIND,
+            GHOST_KIND,
+            "the {GHOST_KIND} kind names the ghost rows"
+        );
+        assert!(
+            self.types.iter().all(|t| t.tag != E::TAG),
+            "replay section {} is used by two event types",
+            String::from_utf8_lossy(&E::TAG)
+        );
+        self.types.push(RecordedEventType {
+            tag: E::TAG,
+            write_history: write_history::<E>,
+            decode_history: decode_history::<E>,
+            discard_from: discard_from::<E>,
+            remap_ghosts: remap_ghosts::<E>,
+            last_ticks: last_ticks::<E>,
+            usage: usage::<E>,
+            ghost_records: ghost_records::<E>,
+            restore_ghost: restore_ghost::<E>,
+            state: state::<E>,
+            markers: markers::<E>,
+            kind: E::KIND,
+            fields: E::FIELDS,
+            to_rows: history_to_rows::<E>,
+            from_rows: history_from_rows::<E>,
+        });
+    }
+
+    /// Writes the history of every recorded event type to the file
+    pub fn write_histories(&self, file: &mut ReplayFile, world: &World) {
+        for recorded in self.types.iter() {
+            (recorded.write_history)(file, world);
+        }
+    }
+
+    /// Inserts the history of every recorded event type from the file,
+    /// returns the tick of the last record
+    ///
+    /// Every section is read first, no history changes if one of them cannot be
+    pub fn insert_histories(
+        &self,
+        file: &ReplayFile,
+        world: &mut World,
+    ) -> Result<u64, ReplayError> {
+        let decoded = self
+            .types
+            .iter()
+            .map(|recorded| (recorded.decode_history)(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(decoded
+            .into_iter()
+            .filter_map(|insert| insert(world))
+            .fold(0, u64::max))
+    }
+
+    /// Forgets the records of a ghost from `tick` onward in every history
+    pub fn discard_from(&self, world: &mut World, ghost: GhostIdentifier, tick: u64) {
+        for recorded in self.types.iter() {
+            (recorded.discard_from)(world, ghost, tick);
+        }
+    }
+
+    /// Copies the records of a ghost in every history
+    pub fn stash_ghost(&self, world: &World, ghost: GhostIdentifier) -> GhostStash {
+        GhostStash {
+            ghost,
+            records: self
+                .types
+                .iter()
+                .map(|recorded| (recorded.ghost_records)(world, ghost))
+                .collect(),
+        }
+    }
+
+    /// Gives its ghost back the records of the stash in every history
+    pub fn restore_ghost(&self, world: &mut World, stash: GhostStash) {
+        for (recorded, records) in self.types.iter().zip(stash.records) {
+            (recorded.restore_ghost)(world, stash.ghost, records);
+        }
+    }
+
+    /// Gives the records of every history their new ghost, see [`LevelHistory::remap_ghosts`]
+    pub fn remap_ghosts(
+        &self,
+        world: &mut World,
+        map: &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>,
+    ) {
+        for recorded in self.types.iter() {
+            (recorded.remap_ghosts)(world, map);
+        }
+    }
+
+    /// The tick of the last record of each of the `count` first ghosts
+    pub fn last_ticks(&self, world: &World, count: usize) -> Vec<u64> {
+        let mut ticks = vec![0; count];
+        for recorded in self.types.iter() {
+            (recorded.last_ticks)(world, &mut ticks);
+        }
+        ticks
+    }
+
+    /// A row for each ghost of the file then every record, sorted by tick
+    pub fn to_rows(&self, file: &ReplayFile) -> Result<Vec<RecordRow>, ReplayError> {
+        let mut rows = ghost_rows(file);
+        for recorded in self.types.iter() {
+            (recorded.to_rows)(file, &mut rows)?;
+        }
+        rows.sort_by_key(|row| row.tick);
+        Ok(rows)
+    }
+
+    /// Builds a replay file from rows
+    ///
+    /// The ghosts get the class of their rows, and are muted by their ghost row.
+    /// Tables without ghost rows only have the ghosts that have records
+    pub fn from_rows(&self, rows: &[RecordRow]) -> Result<ReplayFile, ReplayError> {
+        let level = rows.first().map(|row| row.level).unwrap_or_default();
+        let mut classes = vec![];
+        let mut muted = vec![];
+        for row in rows.iter() {
+            if row.level != level {
+                return Err(ReplayError::Table(format!(
+                    "rows of levels {level} and {}",
+                    row.level
+                )));
+            }
+            if row.kind == GHOST_KIND {
+                if row.payload.get(MUTED_FIELD).is_some_and(|m| *m != 0.0) {
+                    muted.push(row.ghost);
+                }
+            } else if self.types.iter().all(|recorded| recorded.kind != row.kind) {
+                return Err(ReplayError::Table(format!("unknown kind {}", row.kind)));
+            }
+            let class = class_from_name(&row.class)
+                .ok_or_else(|| ReplayError::Table(format!("unknown class {}", row.class)))?;
+            if classes.len() <= row.ghost {
+                classes.resize(row.ghost + 1, None);
+            }
+            match classes[row.ghost] {
+                Some(c) if c != class => {
+                    return Err(ReplayError::Table(format!(
+                        "ghost {} is both {c:?} and {class:?}",
+                        row.ghost
+                    )))
+                }
+                _ => classes[row.ghost] = Some(class),
+            }
+        }
+
+        let mut file = ReplayFile::default();
+        file.level = level;
+        file.ghosts = classes
+            .into_iter()
+            .enumerate()
+            .map(|(ghost, class)| class.ok_or(ReplayError::UnknownGhost(ghost)))
+            .collect::<Result<_, _>>()?;
+        muted.sort_unstable();
+        muted.dedup();
+        file.muted = muted;
+        for recorded in self.types.iter() {
+            (recorded.from_rows)(&mut file, rows);
+        }
+        Ok(file)
+    }
+
+    /// Names of the CSV columns, whether a ghost is muted and the payload values
+    /// of every event type follow the record
+    pub fn csv_columns(&self) -> Vec<&'static str> {
+        let mut columns = CSV_COLUMNS.to_vec();
+        columns.push(MUTED_FIELD);
+        for field in self.types.iter().flat_map(|recorded| recorded.fields) {
+            if !columns.contains(field) {
+                columns.push(field);
+            }
+        }
+        columns
+    }
+
+    /// The number of recorded event types
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Where the history of the event type at `index` is at
+    pub fn state(&self, world: &World, index: usize) -> HistoryState {
+        (self.types[index].state)(world)
+    }
+
+    /// Adds the markers of the event type at `index`, of every record
+    /// or of the records of a ghost from a tick onward
+    pub fn markers(
+        &self,
+        world: &World,
+        index: usize,
+        from: Option<(GhostIdentifier, u64)>,
+        markers: &mut Vec<RecordMarker>,
+    ) {
+        (self.types[index].markers)(world, from, markers);
+    }
+
+    /// The records and memory of all the histories together
+    pub fn usage(&self, world: &World) -> HistoryUsage {
+        self.types
+            .iter()
+            .map(|recorded| (recorded.usage)(world))
+            .fold(HistoryUsage::default(), |total, usage| HistoryUsage {
+                records: total.records + usage.records,
+                bytes: total.bytes + usage.bytes,
+            })
+    }
+}
+
+/// The ghosts of the levels that are not being played, by level id
+///
+/// They are kept like replay files so every recorded event type comes back with them
+#[derive(Resource, Default)]
+pub struct GhostRosters(pub HashMap<usize, ReplayFile>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(x: f32) -> PlayerActionEvent {
+        PlayerActionEvent {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            action: PlayerAction {
+                movement: Vec2::new(x, 0.0),
+                ..default()
+            },
+        }
+    }
+
+    fn record(ghost: usize, tick: u64, x: f32) -> EventRecord<PlayerActionEvent> {
+        EventRecord {
+            ghost: GhostIdentifier(ghost),
+            tick,
+            event: action(x),
+        }
+    }
+
+    fn records(history: &LevelHistory<PlayerActionEvent>) -> Vec<(usize, u64, f32)> {
+        history
+            .events()
+            .iter()
+            .map(|r| (r.ghost.0, r.tick, r.event.action.movement.x))
+            .collect()
+    }
+
+    fn death(ghost: usize, killer: Killer) -> GhostKilledEvent {
+        GhostKilledEvent {
+            source: EventSource::Replay,
+            ghost: GhostIdentifier(ghost),
+            killer,
+            position: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn push_change() {
+        let mut history = LevelHistory::default();
+        assert!(history.push_change(record(0, 1, 1.0)));
+        assert!(!history.push_change(record(0, 2, 1.0)));
+        // Only the last record of the same ghost is compared
+        assert!(history.push_change(record(1, 3, 1.0)));
+        assert!(history.push_change(record(0, 4, 0.5)));
+        assert!(!history.push_change(record(0, 5, 0.5)));
+        assert!(history.push_change(record(0, 6, 1.0)));
+        assert_eq!(
+            records(&history),
+            vec![(0, 1, 1.0), (1, 3, 1.0), (0, 4, 0.5), (0, 6, 1.0)]
+        );
+        assert_eq!(history.last_tick(GhostIdentifier(0)), Some(6));
+    }
+
+    #[test]
+    fn due() {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 0, 1.0));
+        history.push(record(1, 2, 2.0));
+        history.push(record(0, 2, 3.0));
+        history.push(record(0, 5, 4.0));
+        assert_eq!(history.due(0).len(), 1);
+        // Tick 1 is never replayed, its records would be skipped
+        let due = history.due(2);
+        assert_eq!(due.len(), 2);
+        assert!(due.iter().all(|r| r.tick == 2));
+        assert!(history.due(3).is_empty());
+        assert_eq!(history.due(5)[0].event.action.movement.x, 4.0);
+
+        history.rewind();
+        assert!(history.due(2).len() == 2);
+    }
+
+    #[test]
+    fn due_for_replayed_ghosts() {
+        let mut history = LevelHistory::default();
+        for tick in 0..3 {
+            history.push(record(0, tick, tick as f32));
+            history.push(record(1, tick, tick as f32));
+        }
+        let dead = GhostIdentifier(1);
+        assert_eq!(history.due_for(0, |ghost| ghost != dead).len(), 1);
+        assert_eq!(history.due_for(1, |ghost| ghost != dead)[0].ghost.0, 0);
+        // The cursor of the stopped ghost did not move
+        assert_eq!(history.ghosts[1].cursor, 0);
+        history.rewind();
+        assert_eq!(history.due(0).len(), 2);
+    }
+
+    #[test]
+    fn earlier_records_stay_due() {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 10, 1.0));
+        history.push(record(0, 20, 2.0));
+        assert_eq!(history.due(10).len(), 1);
+        history.push(record(0, 5, 3.0));
+        history.push(record(0, 15, 4.0));
+        assert_eq!(
+            records(&history),
+            vec![(0, 5, 3.0), (0, 10, 1.0), (0, 15, 4.0), (0, 20, 2.0)]
+        );
+        // The records after the cursor are still due
+        assert_eq!(history.due(15)[0].event.action.movement.x, 4.0);
+        assert_eq!(history.due(20)[0].event.action.movement.x, 2.0);
+    }
+
+    #[test]
+    fn discard_from() {
+        let mut history = LevelHistory::default();
+        for tick in 0..5 {
+            history.push(record(0, tick * 10, tick as f32));
+            history.push(record(1, tick * 10, tick as f32));
+        }
+        history.discard_from(GhostIdentifier(1), 25);
+        assert_eq!(history.len(), 8);
+        assert_eq!(history.last_tick(GhostIdentifier(1)), Some(20));
+        // The ghost records again from where it was discarded
+        assert!(!history.push_change(record(1, 25, 2.0)));
+        assert!(history.push_change(record(1, 30, 5.0)));
+        assert_eq!(history.last_tick(GhostIdentifier(1)), Some(30));
+
+        history.discard_from(GhostIdentifier(0), 0);
+        assert_eq!(history.last_tick(GhostIdentifier(0)), None);
+        assert_eq!(history.len(), 4);
+    }
+
+    #[test]
+    fn remap_ghosts() {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 1, 0.0));
+        history.push(record(1, 2, 1.0));
+        history.push(record(2, 3, 2.0));
+        history.remap_ghosts(&|id| match id.0 {
+            0 => None,
+            i => Some(GhostIdentifier(i - 1)),
+        });
+        assert_eq!(records(&history), vec![(0, 2, 1.0), (1, 3, 2.0)]);
+    }
+
+    #[test]
+    fn same_tick_records_are_all_kept() {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 10, 1.0));
+        history.push(record(0, 20, 2.0));
+        // Identical records at the same tick are both kept
+        history.push(record(0, 10, 1.0));
+        history.push(record(0, 10, 3.0));
+        assert_eq!(
+            records(&history),
+            vec![(0, 10, 1.0), (0, 10, 1.0), (0, 10, 3.0), (0, 20, 2.0)]
+        );
+    }
+
+    #[test]
+    fn remap_named_ghosts() {
+        let mut history = LevelHistory::default();
+        for (dead, killer) in [
+            (0, Killer::Player),
+            (1, Killer::Ghost(GhostIdentifier(0))),
+            (2, Killer::Ghost(GhostIdentifier(1))),
+        ] {
+            history.push(EventRecord {
+                ghost: GhostIdentifier(3),
+                tick: dead as u64,
+                event: death(dead, killer),
+            });
+        }
+        history.remap_ghosts(&|id| match id.0 {
+            0 => None,
+            i => Some(GhostIdentifier(i - 1)),
+        });
+        let deaths = history
+            .events()
+            .iter()
+            .map(|r| (r.ghost.0, r.event.ghost.0, r.event.killer))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deaths,
+            vec![
+                (2, 0, Killer::Unknown),
+                (2, 1, Killer::Ghost(GhostIdentifier(0)))
+            ]
+        );
+    }
+
+    #[test]
+    fn restore_ghost() {
+        let mut world = World::new();
+        let mut recorded_events = RecordedEvents::default();
+        recorded_events.register::<PlayerActionEvent>();
+        let mut history = LevelHistory::default();
+        for tick in 0..4 {
+            history.push(record(0, tick * 10, tick as f32));
+        }
+        world.insert_resource(history);
+
+        let stash = recorded_events.stash_ghost(&world, GhostIdentifier(0));
+        // Taken over at tick 15, then the cycle is abandoned
+        recorded_events.discard_from(&mut world, GhostIdentifier(0), 15);
+        world
+            .resource_mut::<LevelHistory<PlayerActionEvent>>()
+            .push(record(0, 20, 9.0));
+        recorded_events.restore_ghost(&mut world, stash);
+        assert_eq!(
+            records(world.resource::<LevelHistory<PlayerActionEvent>>()),
+            vec![(0, 0, 0.0), (0, 10, 1.0), (0, 20, 2.0), (0, 30, 3.0)]
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+
+use super::data::GhostIdentifier;
 use crate::player::prelude::*;
 use bevy::prelude::*;
 
@@ -5,3 +8,46 @@ use bevy::prelude::*;
 pub struct SavePlayerGhostEvent {
     pub class: Class,
 }
+
+/// Requests the ghosts of a level to be saved to disk
+#[derive(Event, Debug, Clone)]
+pub struct SaveReplayEvent {
+    pub level: usize,
+    pub path: PathBuf,
+    /// Class of the live player, its run is saved as the last ghost
+    pub player: Option<Class>,
+}
+
+/// Requests saved ghosts of the current level to be loaded from disk
+#[derive(Event, Debug, Clone)]
+pub struct LoadReplayEvent {
+    pub path: PathBuf,
+}
+
+/// Wipes the ghosts of a level
+#[derive(Event, Debug, Clone)]
+pub struct ResetLevelEvent {
+    pub level: usize,
+}
+
+/// Changes the ghosts of the current level between two cycles
+#[derive(Event, Debug, Clone, Copy)]
+pub enum EditGhostEvent {
+    Delete(GhostIdentifier),
+    /// Mutes the ghost, or brings it back
+    ToggleMute(GhostIdentifier),
+    /// Swaps the ghost with the one recorded before it
+    MoveUp(GhostIdentifier),
+    /// Deletes the ghost of the most recent cycle
+    UndoCycle,
+}
+
+/// Sent when a replayed ghost no longer follows its recording
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GhostDivergedEvent {
+    pub ghost: GhostIdentifier,
+    pub tick: u64,
+    /// Distance between the ghost and its recorded keyframe,
+    /// infinite when the ghost was killed before the end of its recording
+    pub distance: f32,
+}
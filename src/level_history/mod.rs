@@ -1,20 +1,30 @@
 mod data;
 mod events;
+mod replay;
 mod systems;
+mod table;
 
 use bevy::prelude::*;
 use data::*;
 use events::*;
+use replay::*;
 use systems::*;
+use table::*;
 
 use crate::game::CurrentLevel;
 use crate::game::GameState;
-use crate::player::prelude::*;
+use crate::game::LevelSetup;
+use crate::game::UnscoredRun;
+use crate::timeline::prelude::FastForward;
+
+pub(crate) use systems::detect_divergence;
 
 pub mod prelude {
     pub use super::data::*;
     pub use super::events::*;
-    pub use super::LevelHistoryPlugin;
+    pub use super::replay::*;
+    pub use super::table::*;
+    pub use super::{LevelHistoryPlugin, RecordedEventAppExt, ReplayFilePlugin};
 }
 
 pub struct LevelHistoryPlugin;
@@ -24,9 +34,12 @@ pub enum LevelHistorySet {
     Replay,
     Record,
     Clear,
-    Debug,
     SavePlayer,
     SpawnGhost,
+    Tick,
+    OpenReplay,
+    ReplaySection,
+    CloseReplay,
 }
 
 fn level_changed(current_level: Res<CurrentLevel>) -> bool {
@@ -36,66 +49,204 @@ fn level_changed(current_level: Res<CurrentLevel>) -> bool {
 impl Plugin for LevelHistoryPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SavePlayerGhostEvent>()
-            .init_resource::<LevelStartTime>()
+            .add_event::<SaveReplayEvent>()
+            .add_event::<LoadReplayEvent>()
+            .add_event::<ResetLevelEvent>()
+            .add_event::<EditGhostEvent>()
+            .add_event::<GhostDivergedEvent>()
+            .init_resource::<LevelTick>()
             .init_resource::<PlayerGhostList>()
+            .init_resource::<PendingReplay>()
+            .init_resource::<KeyframeCorrection>()
+            .init_resource::<RecordedEvents>()
+            .init_resource::<GhostRosters>()
+            .init_resource::<GhostDivergences>()
+            .init_resource::<GhostCasualties>()
+            .init_resource::<GhostRecordingEnds>()
+            .init_resource::<HistoryUsage>()
+            .init_resource::<ForkedRecords>()
             .configure_sets(
+                PostUpdate,
+                (
+                    LevelHistorySet::OpenReplay,
+                    LevelHistorySet::ReplaySection,
+                    LevelHistorySet::CloseReplay,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    open_replay_file.in_set(LevelHistorySet::OpenReplay),
+                    close_replay_file.in_set(LevelHistorySet::CloseReplay),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::LevelSelection),
+                (
+                    restore_forked_ghost.before(store_level_roster),
+                    store_level_roster.before(LevelHistorySet::Clear),
+                    (clear_ghost_list, clear_casualties).in_set(LevelHistorySet::Clear),
+                ),
+            )
+            .add_systems(
                 Update,
-                LevelHistorySet::Clear
-                    .after(LevelHistorySet::Record)
-                    .run_if(level_changed),
+                (
+                    restore_level_roster.run_if(level_changed),
+                    reset_level,
+                    // Ghosts are only edited while none of them is spawned
+                    edit_ghosts.run_if(in_state(GameState::CharacterSelection)),
+                )
+                    .chain(),
             )
-            .configure_sets(OnEnter(GameState::LevelSelection), LevelHistorySet::Clear)
             .configure_sets(
-                Update,
+                FixedUpdate,
                 (
                     LevelHistorySet::Replay,
                     LevelHistorySet::Record,
-                    LevelHistorySet::Clear,
-                    LevelHistorySet::Debug,
+                    LevelHistorySet::Tick,
                 )
+                    .chain()
                     .run_if(in_state(GameState::Play)),
             )
             .add_systems(
-                OnEnter(GameState::Play),
+                LevelSetup,
                 (
                     spawn_ghosts.in_set(LevelHistorySet::SpawnGhost),
-                    reset_level_start_time,
+                    measure_ghost_recordings,
+                    reset_level_tick,
+                    clear_divergences,
+                    clear_casualties,
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_ghost_deaths
+                        .before(LevelHistorySet::Replay)
+                        .run_if(in_state(GameState::Play)),
+                    advance_level_tick.in_set(LevelHistorySet::Tick),
                 ),
             )
             .add_systems(
                 OnEnter(GameState::GameOver),
-                save_player_ghost.in_set(LevelHistorySet::SavePlayer),
+                save_player_ghost
+                    .in_set(LevelHistorySet::SavePlayer)
+                    .run_if(not(resource_exists::<UnscoredRun>)),
             )
-            .add_systems(Update, (save_player_ghost, clean_ghost_list))
-            .init_resource::<LevelHistory<PlayerMoveEvent>>()
             .add_systems(
                 Update,
                 (
-                    record_event::<PlayerMoveEvent>.in_set(LevelHistorySet::Record),
-                    replay_event::<PlayerMoveEvent>.in_set(LevelHistorySet::Replay),
-                    clear_history::<PlayerMoveEvent>.in_set(LevelHistorySet::Clear),
-                    debug_history::<PlayerMoveEvent>.in_set(LevelHistorySet::Debug),
+                    // An unscored run, like a playtest of the editor, is not kept
+                    save_player_ghost.run_if(not(resource_exists::<UnscoredRun>)),
+                    clean_ghost_list,
+                    collect_divergences,
+                    measure_history,
                 ),
             )
-            .init_resource::<LevelHistory<PlayerRotateEvent>>()
+            .add_systems(PostUpdate, commit_forked_records);
+    }
+}
+
+/// Opts an event type in to the level history
+///
+/// Also implemented by a bare [`RecordedEvents`], for tools that only read and write
+/// replay files and need no app
+pub trait RecordedEventAppExt {
+    /// Records the events of type `E` sent by the live player,
+    /// replays them for its ghosts and saves them in replay files
+    ///
+    /// Also registers the event itself, calling it again does nothing
+    fn add_recorded_event<E>(&mut self) -> &mut Self
+    where
+        E: Event
+            + Clone
+            + std::fmt::Debug
+            + SetEntity
+            + EventSourceMethods
+            + EventRecordDebug
+            + ReplayCodec
+            + RecordFields;
+}
+
+impl RecordedEventAppExt for App {
+    fn add_recorded_event<E>(&mut self) -> &mut Self
+    where
+        E: Event
+            + Clone
+            + std::fmt::Debug
+            + SetEntity
+            + EventSourceMethods
+            + EventRecordDebug
+            + ReplayCodec
+            + RecordFields,
+    {
+        if self.world().contains_resource::<LevelHistory<E>>() {
+            return self;
+        }
+        self.world_mut()
+            .get_resource_or_insert_with(RecordedEvents::default)
+            .add_recorded_event::<E>();
+        self.add_event::<E>()
+            .init_resource::<LevelHistory<E>>()
+            .add_systems(LevelSetup, rewind_history::<E>)
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
-                    record_event::<PlayerRotateEvent>.in_set(LevelHistorySet::Record),
-                    replay_event::<PlayerRotateEvent>.in_set(LevelHistorySet::Replay),
-                    clear_history::<PlayerRotateEvent>.in_set(LevelHistorySet::Clear),
-                    debug_history::<PlayerRotateEvent>.in_set(LevelHistorySet::Debug),
+                    // What happens again while a seek simulates the cycle is already recorded
+                    record_event::<E>
+                        .in_set(LevelHistorySet::Record)
+                        .run_if(not(resource_exists::<FastForward>)),
+                    replay_event::<E>.in_set(LevelHistorySet::Replay),
                 ),
             )
-            .init_resource::<LevelHistory<PlayerAttackEvent>>()
             .add_systems(
-                Update,
-                (
-                    record_event::<PlayerAttackEvent>.in_set(LevelHistorySet::Record),
-                    replay_event::<PlayerAttackEvent>.in_set(LevelHistorySet::Replay),
-                    clear_history::<PlayerAttackEvent>.in_set(LevelHistorySet::Clear),
-                    debug_history::<PlayerAttackEvent>.in_set(LevelHistorySet::Debug),
-                ),
-            );
+                OnEnter(GameState::LevelSelection),
+                clear_history::<E>.in_set(LevelHistorySet::Clear),
+            )
+            .add_systems(
+                PostUpdate,
+                save_replay_section::<E>.in_set(LevelHistorySet::ReplaySection),
+            )
+    }
+}
+
+impl RecordedEventAppExt for RecordedEvents {
+    fn add_recorded_event<E>(&mut self) -> &mut Self
+    where
+        E: Event
+            + Clone
+            + std::fmt::Debug
+            + SetEntity
+            + EventSourceMethods
+            + EventRecordDebug
+            + ReplayCodec
+            + RecordFields,
+    {
+        if !self.contains::<E>() {
+            self.register::<E>();
+        }
+        self
+    }
+}
+
+/// Lets the player save and load the ghosts of a level, and keeps the winning runs
+/// on disk while [`SaveWinningRuns`] is present
+///
+/// Left out of headless apps so they never write to the saves
+pub struct ReplayFilePlugin;
+
+impl Plugin for ReplayFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            request_replay_file.run_if(in_state(GameState::CharacterSelection)),
+        )
+        .add_systems(
+            PostUpdate,
+            save_winning_run
+                .before(LevelHistorySet::OpenReplay)
+                .run_if(resource_exists::<SaveWinningRuns>),
+        );
     }
 }
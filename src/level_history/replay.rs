@@ -0,0 +1,745 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::data::*;
+use crate::player::prelude::*;
+use bevy::prelude::*;
+
+/// First bytes of every replay file
+pub const REPLAY_MAGIC: [u8; 4] = *b"COTF";
+
+/// Version of the replay file format
+///
+/// Bump it whenever the layout of a section changes, a section that can be
+/// left out does not need a new version.
+/// - 1 recorded the resolved movements of the players instead of their actions,
+///   its runs cannot be replayed anymore
+/// - 2 recorded the actions, each record stored its ghost as a `u32` and its tick as a `u64`
+/// - 3 stores the ghost and the ticks since the previous record as varints
+pub const REPLAY_VERSION: u16 = 3;
+
+/// Oldest version of the replay file format that can still be read
+pub const OLDEST_REPLAY_VERSION: u16 = 2;
+
+/// Folder where the runs are saved
+pub const REPLAY_DIRECTORY: &str = "saves";
+
+/// Tag of the section holding the level and the ghost classes
+const LEVEL_SECTION: [u8; 4] = *b"LEVL";
+
+/// Tag of the section listing the muted ghosts, it is left out when none is
+const MUTE_SECTION: [u8; 4] = *b"MUTE";
+
+/// Path of the saved run of a level
+pub fn replay_path(level: usize) -> PathBuf {
+    Path::new(REPLAY_DIRECTORY).join(format!("level_{level}.replay"))
+}
+
+/// Path of the last run that completed a level
+pub fn winning_replay_path(level: usize) -> PathBuf {
+    Path::new(REPLAY_DIRECTORY).join(format!("level_{level}_win.replay"))
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    /// The file does not start with [`REPLAY_MAGIC`]
+    NotAReplay,
+    UnsupportedVersion(u16),
+    /// The content of a section does not match its checksum
+    Checksum([u8; 4]),
+    /// The file ended in the middle of a value
+    Truncated,
+    UnknownClass(u8),
+    UnknownKiller(u8),
+    /// A record references a ghost that is not in the file
+    UnknownGhost(usize),
+    MissingSection([u8; 4]),
+    /// The level of the file does not exist in the game
+    UnknownLevel(usize),
+    /// An exported table could not be read back
+    Table(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "could not access the replay file: {e}"),
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported replay version {v}, expected {OLDEST_REPLAY_VERSION} to {REPLAY_VERSION}"
+                )
+            }
+            ReplayError::Checksum(tag) => {
+                write!(f, "corrupted section {}", String::from_utf8_lossy(tag))
+            }
+            ReplayError::Truncated => write!(f, "the replay file is truncated"),
+            ReplayError::UnknownClass(c) => write!(f, "unknown class {c}"),
+            ReplayError::UnknownKiller(k) => write!(f, "unknown killer {k}"),
+            ReplayError::UnknownGhost(g) => write!(f, "record of unknown ghost {g}"),
+            ReplayError::MissingSection(tag) => {
+                write!(f, "missing section {}", String::from_utf8_lossy(tag))
+            }
+            ReplayError::UnknownLevel(level) => write!(f, "unknown level {level}"),
+            ReplayError::Table(message) => write!(f, "invalid table: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(value: std::io::Error) -> Self {
+        ReplayError::Io(value)
+    }
+}
+
+/// Appends little endian values to a buffer
+#[derive(Default)]
+pub struct ReplayWriter(pub Vec<u8>);
+
+impl ReplayWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+    pub fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    /// Writes 7 bits per byte, small values take a single byte
+    pub fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.0.extend_from_slice(value);
+    }
+}
+
+/// Reads little endian values from a buffer
+pub struct ReplayReader<'a>(pub &'a [u8]);
+
+impl<'a> ReplayReader<'a> {
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < len {
+            return Err(ReplayError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self
+            .bytes(N)?
+            .try_into()
+            .expect("slice has the requested length"))
+    }
+    pub fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.array::<1>()?[0])
+    }
+    pub fn u16(&mut self) -> Result<u16, ReplayError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    pub fn u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    pub fn u64(&mut self) -> Result<u64, ReplayError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    pub fn f32(&mut self) -> Result<f32, ReplayError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+    pub fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Truncated)
+    }
+}
+
+/// Events that can be written to a replay file
+///
+/// The entity of the event is not stored, events are decoded with
+/// [`Entity::PLACEHOLDER`] and [`SetEntity`] gives them their ghost on replay
+pub trait ReplayCodec: Sized {
+    /// Identifies the section of the event type in the file
+    const TAG: [u8; 4];
+    /// Whether an event holds until the next one of its ghost,
+    /// the history then skips the events that change nothing
+    const HELD: bool = false;
+    /// Whether the payload names ghosts, see [`ReplayCodec::remap_ghosts`]
+    const NAMES_GHOSTS: bool = false;
+    fn encode(&self, writer: &mut ReplayWriter);
+    fn decode(reader: &mut ReplayReader) -> Result<Self, ReplayError>;
+    /// Gives the ghosts named by the payload their new identifier,
+    /// returns false when the record must be dropped
+    fn remap_ghosts(&mut self, _map: &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>) -> bool {
+        true
+    }
+}
+
+fn encode_class(class: Class) -> u8 {
+    match class {
+        Class::Knight => 0,
+        Class::Ranger => 1,
+        Class::Wizard => 2,
+    }
+}
+
+fn decode_class(value: u8) -> Result<Class, ReplayError> {
+    match value {
+        0 => Ok(Class::Knight),
+        1 => Ok(Class::Ranger),
+        2 => Ok(Class::Wizard),
+        c => Err(ReplayError::UnknownClass(c)),
+    }
+}
+
+/// CRC-32 (IEEE) of the given bytes
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// A saved run of a level
+///
+/// The file starts with [`REPLAY_MAGIC`] and [`REPLAY_VERSION`], followed by sections.
+/// Each section has a tag, a length and a checksum of its content.
+/// The level section holds the level id and the class of every ghost,
+/// then each recorded event type has its own section.
+/// Its records are sorted by tick, each one stores its ghost and
+/// the ticks since the previous record as varints.
+/// Muted ghosts are listed in a section of their own.
+pub struct ReplayFile {
+    pub level: usize,
+    pub ghosts: Vec<Class>,
+    /// Indices of the ghosts that do not spawn
+    pub muted: Vec<usize>,
+    /// Version of the layout of the sections, older files keep theirs
+    version: u16,
+    sections: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl Default for ReplayFile {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            ghosts: vec![],
+            muted: vec![],
+            version: REPLAY_VERSION,
+            sections: vec![],
+        }
+    }
+}
+
+impl ReplayFile {
+    pub fn new(level: usize, ghost_list: &PlayerGhostList) -> Self {
+        Self {
+            level,
+            ghosts: ghost_list.ghosts.iter().map(|g| g.class).collect(),
+            muted: ghost_list
+                .ghosts
+                .iter()
+                .enumerate()
+                .filter(|(_, g)| g.muted)
+                .map(|(i, _)| i)
+                .collect(),
+            ..default()
+        }
+    }
+
+    /// Stores the records of the saved ghosts
+    ///
+    /// Records of the live player are not part of a ghost yet and are left out
+    pub fn write_history<E: Event + ReplayCodec>(&mut self, history: &LevelHistory<E>) {
+        debug_assert_eq!(
+            self.version, REPLAY_VERSION,
+            "records are only written in the current layout"
+        );
+        let records = history
+            .events()
+            .into_iter()
+            .filter(|r| r.ghost.0 < self.ghosts.len())
+            .collect::<Vec<_>>();
+        let mut writer = ReplayWriter::default();
+        writer.u32(records.len() as u32);
+        let mut tick = 0;
+        for record in records {
+            writer.varint(record.ghost.0 as u64);
+            writer.varint(record.tick - tick);
+            record.event.encode(&mut writer);
+            tick = record.tick;
+        }
+        self.sections.retain(|(tag, _)| *tag != E::TAG);
+        self.sections.push((E::TAG, writer.0));
+    }
+
+    /// Reads back the records of an event type
+    ///
+    /// A file without a section for the event type has no record of it,
+    /// so files saved before an event type was recorded stay readable.
+    /// Records of older files are read with the layout of their version
+    pub fn read_history<E: Event + ReplayCodec>(&self) -> Result<LevelHistory<E>, ReplayError> {
+        let mut history = LevelHistory::default();
+        let Some((_, payload)) = self.sections.iter().find(|(tag, _)| *tag == E::TAG) else {
+            return Ok(history);
+        };
+        let mut reader = ReplayReader(payload);
+        let mut tick = 0u64;
+        for _ in 0..reader.u32()? {
+            let ghost = match self.version {
+                2 => reader.u32()? as usize,
+                _ => reader.varint()? as usize,
+            };
+            if ghost >= self.ghosts.len() {
+                return Err(ReplayError::UnknownGhost(ghost));
+            }
+            tick = match self.version {
+                2 => reader.u64()?,
+                _ => tick.saturating_add(reader.varint()?),
+            };
+            history.push(EventRecord {
+                ghost: GhostIdentifier(ghost),
+                tick,
+                event: E::decode(&mut reader)?,
+            });
+        }
+        Ok(history)
+    }
+
+    /// The ghosts of the file, they get their entity when spawned
+    pub fn ghost_list(&self) -> PlayerGhostList {
+        PlayerGhostList {
+            ghosts: self
+                .ghosts
+                .iter()
+                .enumerate()
+                .map(|(i, class)| PlayerGhost {
+                    entity: None,
+                    class: *class,
+                    muted: self.muted.contains(&i),
+                })
+                .collect(),
+            forked: None,
+            forked_at: 0,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut level = ReplayWriter::default();
+        level.u64(self.level as u64);
+        level.u32(self.ghosts.len() as u32);
+        for class in self.ghosts.iter() {
+            level.u8(encode_class(*class));
+        }
+        let mut mute = ReplayWriter::default();
+        mute.u32(self.muted.len() as u32);
+        for ghost in self.muted.iter() {
+            mute.u32(*ghost as u32);
+        }
+
+        let mut sections = vec![(&LEVEL_SECTION, &level.0)];
+        if !self.muted.is_empty() {
+            sections.push((&MUTE_SECTION, &mute.0));
+        }
+        sections.extend(self.sections.iter().map(|(tag, payload)| (tag, payload)));
+
+        let mut writer = ReplayWriter::default();
+        writer.bytes(&REPLAY_MAGIC);
+        writer.u16(self.version);
+        writer.u16(sections.len() as u16);
+        for (tag, payload) in sections {
+            writer.bytes(tag);
+            writer.u32(payload.len() as u32);
+            writer.u32(checksum(payload));
+            writer.bytes(payload);
+        }
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = ReplayReader(bytes);
+        if reader.bytes(4).map_err(|_| ReplayError::NotAReplay)? != REPLAY_MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let version = reader.u16()?;
+        if !(OLDEST_REPLAY_VERSION..=REPLAY_VERSION).contains(&version) {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let mut file = ReplayFile {
+            version,
+            ..default()
+        };
+        let mut level_section = None;
+        let mut mute_section = None;
+        for _ in 0..reader.u16()? {
+            let tag: [u8; 4] = reader.array()?;
+            let len = reader.u32()? as usize;
+            let crc = reader.u32()?;
+            let payload = reader.bytes(len)?;
+            if checksum(payload) != crc {
+                return Err(ReplayError::Checksum(tag));
+            }
+            if tag == LEVEL_SECTION {
+                level_section = Some(payload);
+            } else if tag == MUTE_SECTION {
+                mute_section = Some(payload);
+            } else {
+                file.sections.push((tag, payload.to_vec()));
+            }
+        }
+
+        let mut level =
+            ReplayReader(level_section.ok_or(ReplayError::MissingSection(LEVEL_SECTION))?);
+        file.level = level.u64()? as usize;
+        for _ in 0..level.u32()? {
+            file.ghosts.push(decode_class(level.u8()?)?);
+        }
+        if let Some(payload) = mute_section {
+            let mut mute = ReplayReader(payload);
+            for _ in 0..mute.u32()? {
+                let ghost = mute.u32()? as usize;
+                if ghost >= file.ghosts.len() {
+                    return Err(ReplayError::UnknownGhost(ghost));
+                }
+                file.muted.push(ghost);
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// Present when the run that completes a level is saved to [`winning_replay_path`],
+/// the game only adds it when started with `--save-winning-runs`
+#[derive(Resource, Debug, Default)]
+pub struct SaveWinningRuns;
+
+/// A replay file being saved or loaded
+///
+/// Every recorded event type writes its own section of a saved file
+/// between its opening and its closing
+#[derive(Resource, Default)]
+pub struct PendingReplay(pub Option<(ReplayMode, ReplayFile)>);
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ReplayMode {
+    Save(PathBuf),
+    Load,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::table::{read_csv, write_csv};
+    use super::*;
+
+    fn keyframe(x: f32) -> PlayerKeyframeEvent {
+        PlayerKeyframeEvent {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            position: Vec2::new(x, -x),
+            rotation: Quat::from_rotation_z(x),
+        }
+    }
+
+    fn record<E: Event>(ghost: usize, tick: u64, event: E) -> EventRecord<E> {
+        EventRecord {
+            ghost: GhostIdentifier(ghost),
+            tick,
+            event,
+        }
+    }
+
+    fn ghost_list() -> PlayerGhostList {
+        PlayerGhostList {
+            ghosts: vec![
+                PlayerGhost {
+                    entity: None,
+                    class: Class::Knight,
+                    muted: false,
+                },
+                PlayerGhost {
+                    entity: None,
+                    class: Class::Wizard,
+                    muted: true,
+                },
+            ],
+            forked: None,
+            forked_at: 0,
+        }
+    }
+
+    /// A file of level 3 with two ghosts, the second one muted
+    fn replay_file() -> ReplayFile {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 0, keyframe(1.0)));
+        history.push(record(1, 36, keyframe(2.0)));
+        history.push(record(0, 72, keyframe(3.0)));
+        history.push(record(1, 1_000_000, keyframe(4.0)));
+        // The live player is not part of the file
+        history.push(record(2, 1_000_001, keyframe(5.0)));
+        let mut file = ReplayFile::new(3, &ghost_list());
+        file.write_history(&history);
+        file
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = ReplayFile::from_bytes(&replay_file().to_bytes()).unwrap();
+        assert_eq!(file.level, 3);
+        assert_eq!(file.ghosts, vec![Class::Knight, Class::Wizard]);
+        assert_eq!(file.muted, vec![1]);
+
+        let history = file.read_history::<PlayerKeyframeEvent>().unwrap();
+        let records = history
+            .events()
+            .iter()
+            .map(|r| (r.ghost.0, r.tick, r.event.position, r.event.rotation))
+            .collect::<Vec<_>>();
+        let expected = [(0, 0, 1.0), (1, 36, 2.0), (0, 72, 3.0), (1, 1_000_000, 4.0)]
+            .map(|(ghost, tick, x)| (ghost, tick, keyframe(x).position, keyframe(x).rotation));
+        assert_eq!(records, expected);
+
+        // A type without section has no record
+        let killed = file.read_history::<PlayerKilledEvent>().unwrap();
+        assert!(killed.events().is_empty());
+    }
+
+    #[test]
+    fn varint() {
+        for (value, len) in [(0, 1), (127, 1), (128, 2), (300, 2), (u64::MAX, 10)] {
+            let mut writer = ReplayWriter::default();
+            writer.varint(value);
+            assert_eq!(writer.0.len(), len, "length of {value}");
+            let mut reader = ReplayReader(&writer.0);
+            assert_eq!(reader.varint().unwrap(), value);
+            assert!(reader.0.is_empty());
+        }
+        assert!(matches!(
+            ReplayReader(&[0x80, 0x80]).varint(),
+            Err(ReplayError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn ticks_are_deltas() {
+        let mut history = LevelHistory::default();
+        history.push(record(0, 1_000_000, keyframe(1.0)));
+        history.push(record(0, 1_000_001, keyframe(2.0)));
+        let mut file = ReplayFile::new(1, &ghost_list());
+        file.write_history(&history);
+
+        let payload = &file.sections[0].1;
+        let event = {
+            let mut writer = ReplayWriter::default();
+            keyframe(1.0).encode(&mut writer);
+            writer.0.len()
+        };
+        // Count, then the ghost and a three byte tick, then the ghost and a one byte delta
+        assert_eq!(payload.len(), 4 + (1 + 3 + event) + (1 + 1 + event));
+        let read = file.read_history::<PlayerKeyframeEvent>().unwrap();
+        let ticks = read.events().iter().map(|r| r.tick).collect::<Vec<_>>();
+        assert_eq!(ticks, vec![1_000_000, 1_000_001]);
+    }
+
+    #[test]
+    fn corrupted_section() {
+        let mut bytes = replay_file().to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        assert!(matches!(
+            ReplayFile::from_bytes(&bytes),
+            Err(ReplayError::Checksum(tag)) if tag == PlayerKeyframeEvent::TAG
+        ));
+    }
+
+    #[test]
+    fn truncated_file() {
+        let bytes = replay_file().to_bytes();
+        for len in 0..bytes.len() {
+            let result = ReplayFile::from_bytes(&bytes[..len]);
+            if len < REPLAY_MAGIC.len() {
+                assert!(matches!(result, Err(ReplayError::NotAReplay)), "{len}");
+            } else {
+                assert!(matches!(result, Err(ReplayError::Truncated)), "{len}");
+            }
+        }
+    }
+
+    #[test]
+    fn other_version() {
+        let mut bytes = replay_file().to_bytes();
+        bytes[4..6].copy_from_slice(&(REPLAY_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ReplayFile::from_bytes(&bytes),
+            Err(ReplayError::UnsupportedVersion(v)) if v == REPLAY_VERSION + 1
+        ));
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert!(matches!(
+            ReplayFile::from_bytes(&bytes),
+            Err(ReplayError::UnsupportedVersion(1))
+        ));
+        assert!(matches!(
+            ReplayFile::from_bytes(b"RIFF\x04\x00"),
+            Err(ReplayError::NotAReplay)
+        ));
+    }
+
+    #[test]
+    fn version_2() {
+        let mut payload = ReplayWriter::default();
+        payload.u32(2);
+        for (ghost, tick, x) in [(1, 36, 2.0), (0, 1_000_000, 3.0)] {
+            payload.u32(ghost);
+            payload.u64(tick);
+            keyframe(x).encode(&mut payload);
+        }
+        let mut file = ReplayFile::new(3, &ghost_list());
+        file.version = 2;
+        file.sections.push((PlayerKeyframeEvent::TAG, payload.0));
+
+        let file = ReplayFile::from_bytes(&file.to_bytes()).unwrap();
+        assert_eq!(file.ghosts, vec![Class::Knight, Class::Wizard]);
+        assert_eq!(file.muted, vec![1]);
+        let history = file.read_history::<PlayerKeyframeEvent>().unwrap();
+        let records = history
+            .events()
+            .iter()
+            .map(|r| (r.ghost.0, r.tick, r.event.position))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                (1, 36, keyframe(2.0).position),
+                (0, 1_000_000, keyframe(3.0).position)
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_ghost() {
+        let mut file = replay_file();
+        file.muted.push(5);
+        assert!(matches!(
+            ReplayFile::from_bytes(&file.to_bytes()),
+            Err(ReplayError::UnknownGhost(5))
+        ));
+
+        let mut payload = ReplayWriter::default();
+        payload.u32(1);
+        payload.varint(2);
+        payload.varint(0);
+        let mut file = replay_file();
+        file.sections.push((PlayerKilledEvent::TAG, payload.0));
+        let file = ReplayFile::from_bytes(&file.to_bytes()).unwrap();
+        assert!(matches!(
+            file.read_history::<PlayerKilledEvent>(),
+            Err(ReplayError::UnknownGhost(2))
+        ));
+    }
+
+    #[test]
+    fn failed_section_inserts_nothing() {
+        let mut recorded_events = RecordedEvents::default();
+        recorded_events.register::<PlayerKeyframeEvent>();
+        recorded_events.register::<PlayerKilledEvent>();
+        let mut world = World::new();
+        let mut kept = LevelHistory::<PlayerKeyframeEvent>::default();
+        kept.push(record(0, 5, keyframe(9.0)));
+        world.insert_resource(kept);
+
+        let mut file = replay_file();
+        let mut payload = ReplayWriter::default();
+        payload.u32(1);
+        payload.varint(0);
+        file.sections.push((PlayerKilledEvent::TAG, payload.0));
+        assert!(matches!(
+            recorded_events.insert_histories(&file, &mut world),
+            Err(ReplayError::Truncated)
+        ));
+        let history = world.resource::<LevelHistory<PlayerKeyframeEvent>>();
+        assert_eq!(history.events().len(), 1);
+        assert_eq!(history.events()[0].tick, 5);
+        assert!(!world.contains_resource::<LevelHistory<PlayerKilledEvent>>());
+
+        file.sections.pop();
+        assert_eq!(
+            recorded_events.insert_histories(&file, &mut world).unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            world
+                .resource::<LevelHistory<PlayerKeyframeEvent>>()
+                .events()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let mut recorded_events = RecordedEvents::default();
+        recorded_events.register::<PlayerKeyframeEvent>();
+        // The third ghost has no record
+        let mut list = ghost_list();
+        list.ghosts.push(PlayerGhost {
+            entity: None,
+            class: Class::Ranger,
+            muted: true,
+        });
+        let mut file = ReplayFile::new(3, &list);
+        file.write_history(&replay_file().read_history::<PlayerKeyframeEvent>().unwrap());
+
+        let rows = recorded_events.to_rows(&file).unwrap();
+        let mut csv = vec![];
+        write_csv(&rows, &recorded_events.csv_columns(), &mut csv).unwrap();
+        let rows = read_csv(&String::from_utf8(csv).unwrap()).unwrap();
+        let imported = recorded_events.from_rows(&rows).unwrap();
+        assert_eq!(imported.ghosts, file.ghosts);
+        assert_eq!(imported.muted, vec![1, 2]);
+        assert_eq!(
+            imported
+                .read_history::<PlayerKeyframeEvent>()
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+}
@@ -1,157 +1,215 @@
 use super::prelude::*;
-use crate::game::GameState;
+use crate::game::{CurrentLevel, GameState, LevelBody, LevelCompletedEvent};
+use crate::levels::prelude::*;
 use crate::player::prelude::*;
-use avian2d::prelude::*;
+use bevy::ecs::event::ManualEventReader;
 use bevy::prelude::*;
 
 /// Records events
 ///
-/// Events have a timestamp and a ghost identifier
+/// Events have a tick and a ghost identifier
 /// The ghost identifier is used to identify the ghost
 /// that caused the event
-pub fn record_event<E: Event + EventSourceMethods + Clone + std::fmt::Debug>(
+/// Held events are only recorded when they change
+pub fn record_event<E: Event + EventSourceMethods + ReplayCodec + Clone + std::fmt::Debug>(
     mut history: ResMut<LevelHistory<E>>,
     ghost_list: Res<PlayerGhostList>,
-    start_time: Res<LevelStartTime>,
-    time: Res<Time>,
+    tick: Res<LevelTick>,
     mut event: EventReader<E>,
 ) {
     for e in event.read() {
         if e.get_source() == EventSource::Replay {
             continue;
         }
-        let timestamp = time.elapsed().as_secs_f64() - start_time.0;
-        let ghost = GhostIdentifier(ghost_list.ghosts.len());
+        let ghost = ghost_list.live();
         let mut recorded_event = e.clone();
         recorded_event.set_source(EventSource::Replay);
-        history.events.push(EventRecord {
+        let record = EventRecord {
             ghost,
-            timestamp,
+            tick: tick.0,
             event: recorded_event,
-        });
+        };
+        if E::HELD {
+            history.push_change(record);
+        } else {
+            history.push(record);
+        }
     }
+}
 
-    if !event.is_empty() {
-        info!("Recorded events: {:?}", history.events.len())
-    }
+/// Keeps [`HistoryUsage`] up to date with the histories of every recorded event type
+pub fn measure_history(world: &mut World) {
+    let usage = world.resource::<RecordedEvents>().usage(world);
+    world.resource_mut::<HistoryUsage>().set_if_neq(usage);
 }
 
 /// Replays events stored in the history
 ///
-/// Sends events that were recorded in a time between now and the previous frame
-pub fn replay_event<E: Event + Clone + std::fmt::Debug + SetEntity>(
-    history: ResMut<LevelHistory<E>>,
-    start_time: Res<LevelStartTime>,
+/// Sends events that were recorded on the current tick.
+/// Records of the live player are only due after a seek,
+/// they drive the live player back to the moment it resumes from,
+/// as do the records of the ghost it took over before the fork.
+/// The records of muted and dead ghosts, which have no entity, are not even read
+pub fn replay_event<E: Event + Clone + std::fmt::Debug + SetEntity + ReplayCodec>(
+    mut history: ResMut<LevelHistory<E>>,
+    tick: Res<LevelTick>,
     mut event_writer: EventWriter<E>,
     ghost_list: Res<PlayerGhostList>,
-    time: Res<Time>,
+    player: Query<Entity, With<Player>>,
+    ghosts: Query<(), With<Ghost>>,
 ) {
-    for record in history.events.iter() {
-        let timestamp = record.timestamp;
-        let delta = time.delta_seconds_f64();
-        let start = time.elapsed().as_secs_f64() - delta - start_time.0;
-        let end = start + delta;
-
-        let is_between = timestamp > start && timestamp <= end;
-
-        if is_between {
-            if let Some(entity) = ghost_list.get_ghost(record.ghost).and_then(|g| g.entity) {
-                let mut event = record.event.clone();
-                event.set_entity(entity);
-                event_writer.send(event);
-            }
+    let live = ghost_list.live();
+    let replayed = |ghost| {
+        ghost == live
+            || ghost_list
+                .get_ghost(ghost)
+                .is_some_and(|g| g.entity.is_some())
+    };
+    for record in history.due_for(tick.0, replayed) {
+        let entity = if record.ghost == ghost_list.live() {
+            player.get_single().ok()
+        } else {
+            ghost_list
+                .get_ghost(record.ghost)
+                .and_then(|g| g.entity)
+                .filter(|e| ghosts.contains(*e))
+        };
+        if let Some(entity) = entity {
+            let mut event = record.event;
+            event.set_entity(entity);
+            event_writer.send(event);
         }
     }
 }
 
-/// Stores the start time of the level
+/// Compares the replayed ghosts with their recorded keyframes
 ///
-/// This is used by the replay system to correctly interpret timestamps
-pub fn reset_level_start_time(mut history: ResMut<LevelStartTime>, time: Res<Time>) {
-    history.0 = time.elapsed().as_secs_f64();
-    info!("Reset game history start time to {}", history.0);
+/// Must run before the ghosts are corrected toward the keyframes
+pub fn detect_divergence<E: Event + EventSourceMethods + KeyframeEvent>(
+    mut events: EventReader<E>,
+    ghosts: Query<(&GhostIdentifier, &Transform), With<Ghost>>,
+    tick: Res<LevelTick>,
+    mut diverged_event: EventWriter<GhostDivergedEvent>,
+) {
+    for event in events.read() {
+        if event.get_source() != EventSource::Replay {
+            continue;
+        }
+        let Ok((ghost, transform)) = ghosts.get(event.entity()) else {
+            continue;
+        };
+        let distance = event.position().distance(transform.translation.truncate());
+        if distance > DIVERGENCE_DISTANCE {
+            diverged_event.send(GhostDivergedEvent {
+                ghost: *ghost,
+                tick: tick.0,
+                distance,
+            });
+        }
+    }
 }
 
-pub fn debug_history<E: Event + EventRecordDebug>(
-    mut commands: Commands,
-    history: Res<LevelHistory<E>>,
-    time: Res<Time>,
-    start_time: Res<LevelStartTime>,
-    mut container_id: Local<Option<Entity>>,
+/// Measures how long each ghost was recorded for, its death is expected at the end
+pub fn measure_ghost_recordings(world: &mut World) {
+    let count = world.resource::<PlayerGhostList>().ghosts.len();
+    let ends = world
+        .resource::<RecordedEvents>()
+        .clone()
+        .last_ticks(world, count);
+    world.insert_resource(GhostRecordingEnds(ends));
+}
+
+/// Keeps the deaths of the ghosts for the cycle summary and the timeline
+///
+/// The ghost is forgotten at once so none of its remaining records is replayed,
+/// a ghost killed before the end of its recording diverged from it.
+/// Replayed deaths are only records of earlier cycles and are skipped
+pub fn record_ghost_deaths(
+    mut ghost_killed_event: EventReader<GhostKilledEvent>,
+    mut ghost_list: ResMut<PlayerGhostList>,
+    tick: Res<LevelTick>,
+    ends: Res<GhostRecordingEnds>,
+    mut casualties: ResMut<GhostCasualties>,
+    mut diverged_event: EventWriter<GhostDivergedEvent>,
 ) {
-    if let Some(entity_commands) = container_id.as_ref().and_then(|e| commands.get_entity(*e)) {
-        entity_commands.despawn_recursive();
-    }
-    let delta = time.delta_seconds_f64();
-    let start = time.elapsed().as_secs_f64() - delta - start_time.0;
-    let scale = ((start / 100.0).min(1.0).ceil() * 100.0) as f32;
-
-    let height = Val::Px(50.0);
-    let container = commands
-        .spawn((
-            NodeBundle {
-                style: Style {
-                    width: Val::Percent(100.0),
-                    height,
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(0.0),
-                    left: Val::Px(0.0),
-                    ..default()
-                },
-                background_color: Color::srgba(0.0, 0.0, 0.0, 0.5).into(),
-                ..default()
-            },
-            StateScoped(GameState::Play),
-        ))
-        .id();
-
-    let current_time_tracker = commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Px(delta as f32 * scale),
-                height,
-                position_type: PositionType::Absolute,
-                top: Val::Px(0.0),
-                left: Val::Px(start as f32 * scale),
-                ..default()
-            },
-            background_color: Color::srgba(1.0, 1.0, 0.0, 0.5).into(),
-            ..default()
-        })
-        .id();
-
-    let mut records = history
-        .events
-        .iter()
-        .map(|record| {
-            let timestamp = record.timestamp;
-            let color = record.event.get_debug_color(record.ghost);
-
-            commands
-                .spawn(NodeBundle {
-                    style: Style {
-                        width: Val::Px(1.0),
-                        height,
-                        position_type: PositionType::Absolute,
-                        top: Val::Px(0.0),
-                        left: Val::Px(timestamp as f32 * scale),
-                        ..default()
-                    },
-                    background_color: color.into(),
-                    ..default()
-                })
-                .id()
-        })
-        .collect::<Vec<_>>();
+    for e in ghost_killed_event.read() {
+        if e.source == EventSource::Replay {
+            continue;
+        }
+        let ghost = &e.ghost;
+        if let Some(g) = ghost_list.ghosts.get_mut(ghost.0) {
+            g.entity = None;
+        }
+        if casualties.0.iter().any(|c| c.ghost == *ghost) {
+            continue;
+        }
+        info!("Ghost {} {} at tick {}", ghost.0 + 1, e.killer, tick.0);
+        casualties.0.push(GhostCasualty {
+            ghost: *ghost,
+            tick: tick.0,
+            killer: e.killer,
+            position: e.position,
+        });
+        if ends.0.get(ghost.0).is_some_and(|end| *end > tick.0) {
+            diverged_event.send(GhostDivergedEvent {
+                ghost: *ghost,
+                tick: tick.0,
+                distance: f32::INFINITY,
+            });
+        }
+    }
+}
 
-    records.push(current_time_tracker);
-    commands.entity(container).push_children(&records);
-    *container_id = Some(container);
+pub fn clear_casualties(mut casualties: ResMut<GhostCasualties>) {
+    casualties.0.clear();
+}
+
+/// Keeps the first divergence of each ghost
+pub fn collect_divergences(
+    mut diverged_event: EventReader<GhostDivergedEvent>,
+    mut divergences: ResMut<GhostDivergences>,
+    ghost_list: Res<PlayerGhostList>,
+) {
+    for e in diverged_event.read() {
+        if divergences.0.iter().any(|d| d.ghost == e.ghost) {
+            continue;
+        }
+        let class = ghost_list.get_ghost(e.ghost).map(|g| g.class);
+        info!(
+            "Ghost {} {:?} diverged at tick {} by {}",
+            e.ghost.0 + 1,
+            class,
+            e.tick,
+            e.distance
+        );
+        divergences.0.push(*e);
+    }
+}
+
+pub fn clear_divergences(mut divergences: ResMut<GhostDivergences>) {
+    divergences.0.clear();
+}
+
+/// Resets the tick counter of the level
+///
+/// This is used by the replay system to correctly interpret ticks
+pub fn reset_level_tick(mut tick: ResMut<LevelTick>) {
+    tick.0 = 0;
+    info!("Reset level tick");
+}
+
+/// Advances the tick counter once every fixed simulation step
+pub fn advance_level_tick(mut tick: ResMut<LevelTick>) {
+    tick.0 += 1;
 }
 
-/// Clears the level history
 pub fn clear_history<T: Event>(mut history: ResMut<LevelHistory<T>>) {
-    history.events.clear();
+    history.clear();
+}
+
+/// Moves the replay cursor back to the start of the level
+pub fn rewind_history<T: Event>(mut history: ResMut<LevelHistory<T>>) {
+    history.rewind();
 }
 
 /// Spawn previous recordings of the player
@@ -163,21 +221,34 @@ pub fn spawn_ghosts(
     mut ghost_list: ResMut<PlayerGhostList>,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    current_level: Res<CurrentLevel>,
+    definitions: Res<Assets<LevelDefinition>>,
 ) {
+    let spawn = current_level
+        .definition(&definitions)
+        .map(|level| level.spawn)
+        .unwrap_or_default();
     let mut count = 0;
-    for g in ghost_list.ghosts.iter_mut() {
+    let forked = ghost_list.forked;
+    for (index, g) in ghost_list.ghosts.iter_mut().enumerate() {
         if let Some(e) = g.entity.and_then(|e| commands.get_entity(e)) {
             warn!("Ghost already exists");
             e.despawn_recursive();
         }
+        if g.muted || forked == Some(GhostIdentifier(index)) {
+            g.entity = None;
+            continue;
+        }
         let entity = commands
             .spawn((
                 Ghost,
-                GhostIdentifier(count),
+                GhostIdentifier(index),
+                LevelBody,
                 StateScoped(GameState::Play),
                 PlayerBundle::new(
                     PlayerType::Ghost,
                     g.class,
+                    spawn,
                     &asset_server,
                     &mut texture_atlas_layouts,
                 ),
@@ -205,6 +276,140 @@ pub fn spawn_ghosts(
     }
 }
 
+/// Puts the ghosts of the roster in play, an empty roster removes them all
+///
+/// Nothing changes when one of its histories cannot be read
+fn load_roster(world: &mut World, roster: &ReplayFile) -> Result<(), ReplayError> {
+    world
+        .resource::<RecordedEvents>()
+        .clone()
+        .insert_histories(roster, world)?;
+    world.insert_resource(roster.ghost_list());
+    Ok(())
+}
+
+/// Removes every ghost of the level and their records
+fn load_empty_roster(world: &mut World, level: usize) {
+    load_roster(world, &ReplayFile::new(level, &default()))
+        .expect("an empty roster has no history to read");
+}
+
+/// Keeps the ghosts of the level that is left, they come back when it is selected again
+pub fn store_level_roster(world: &mut World) {
+    let Some(level) = world.resource::<CurrentLevel>().0.as_ref().map(|l| l.id) else {
+        return;
+    };
+    let mut roster = ReplayFile::new(level, world.resource::<PlayerGhostList>());
+    world
+        .resource::<RecordedEvents>()
+        .clone()
+        .write_histories(&mut roster, world);
+    world.resource_mut::<GhostRosters>().0.insert(level, roster);
+}
+
+/// Brings back the ghosts of the selected level
+pub fn restore_level_roster(world: &mut World) {
+    let level = world.resource::<CurrentLevel>().0.as_ref().map(|l| l.id);
+    let roster = level
+        .and_then(|id| world.resource_mut::<GhostRosters>().0.remove(&id))
+        .unwrap_or_else(|| ReplayFile::new(level.unwrap_or_default(), &default()));
+    match load_roster(world, &roster) {
+        Ok(()) if !roster.ghosts.is_empty() => info!(
+            "Restored {} ghosts of level {}",
+            roster.ghosts.len(),
+            roster.level
+        ),
+        Ok(()) => {}
+        Err(e) => {
+            warn!(
+                "Could not restore the ghosts of level {}: {e}",
+                roster.level
+            );
+            load_empty_roster(world, roster.level);
+        }
+    }
+}
+
+/// Wipes the ghosts of a level, stored or in play
+pub fn reset_level(world: &mut World, mut reader: Local<ManualEventReader<ResetLevelEvent>>) {
+    let levels = reader
+        .read(world.resource::<Events<ResetLevelEvent>>())
+        .map(|e| e.level)
+        .collect::<Vec<_>>();
+    for level in levels {
+        world.resource_mut::<GhostRosters>().0.remove(&level);
+        if world
+            .resource::<CurrentLevel>()
+            .0
+            .as_ref()
+            .is_some_and(|l| l.id == level)
+        {
+            load_empty_roster(world, level);
+        }
+        info!("Reset the ghosts of level {level}");
+    }
+}
+
+/// Removes a ghost and its records, the ghosts after it move down one index
+fn delete_ghost(world: &mut World, recorded_events: &RecordedEvents, ghost: usize) {
+    world.resource_mut::<PlayerGhostList>().ghosts.remove(ghost);
+    recorded_events.remap_ghosts(world, &|id| match id.0 {
+        i if i == ghost => None,
+        i if i > ghost => Some(GhostIdentifier(i - 1)),
+        _ => Some(id),
+    });
+}
+
+/// Deletes, mutes or reorders the ghosts of the current level
+///
+/// The ghosts are identified by their index, so the records of every history
+/// are given the new index of their ghost
+pub fn edit_ghosts(world: &mut World, mut reader: Local<ManualEventReader<EditGhostEvent>>) {
+    let edits = reader
+        .read(world.resource::<Events<EditGhostEvent>>())
+        .copied()
+        .collect::<Vec<_>>();
+    let recorded_events = world.resource::<RecordedEvents>().clone();
+    for edit in edits {
+        let count = world.resource::<PlayerGhostList>().ghosts.len();
+        match edit {
+            EditGhostEvent::Delete(GhostIdentifier(ghost)) if ghost < count => {
+                delete_ghost(world, &recorded_events, ghost);
+                info!("Deleted ghost {}", ghost + 1);
+            }
+            EditGhostEvent::UndoCycle if count > 0 => {
+                delete_ghost(world, &recorded_events, count - 1);
+                info!("Undid cycle {count}");
+            }
+            EditGhostEvent::ToggleMute(GhostIdentifier(ghost)) if ghost < count => {
+                let mut ghost_list = world.resource_mut::<PlayerGhostList>();
+                let muted = &mut ghost_list.ghosts[ghost].muted;
+                *muted = !*muted;
+                info!("Ghost {} muted: {}", ghost + 1, *muted);
+            }
+            EditGhostEvent::MoveUp(GhostIdentifier(ghost)) if ghost > 0 && ghost < count => {
+                world
+                    .resource_mut::<PlayerGhostList>()
+                    .ghosts
+                    .swap(ghost - 1, ghost);
+                recorded_events.remap_ghosts(world, &|id| match id.0 {
+                    i if i == ghost => Some(GhostIdentifier(ghost - 1)),
+                    i if i == ghost - 1 => Some(GhostIdentifier(ghost)),
+                    _ => Some(id),
+                });
+                info!("Moved ghost {} up", ghost + 1);
+            }
+            edit => warn!("Cannot apply {edit:?} to {count} ghosts"),
+        }
+    }
+}
+
+pub fn clear_ghost_list(mut ghost_list: ResMut<PlayerGhostList>) {
+    ghost_list.ghosts.clear();
+    ghost_list.forked = None;
+    ghost_list.forked_at = 0;
+}
+
 pub fn clean_ghost_list(
     mut ghost_list: ResMut<PlayerGhostList>,
     mut ghost_id_removed: RemovedComponents<GhostIdentifier>,
@@ -218,14 +423,153 @@ pub fn clean_ghost_list(
     }
 }
 
+/// Turns the run of the live player into a ghost
+///
+/// A run forked from a ghost is already recorded under that ghost, it keeps its class
 pub fn save_player_ghost(
     mut ghost_list: ResMut<PlayerGhostList>,
+    mut forked_records: ResMut<ForkedRecords>,
     mut save_event: EventReader<SavePlayerGhostEvent>,
 ) {
     for e in save_event.read() {
+        if let Some(ghost) = ghost_list.forked.take() {
+            forked_records.0 = None;
+            info!("Ghost {} was forked", ghost.0 + 1);
+            continue;
+        }
         ghost_list.ghosts.push(PlayerGhost {
             class: e.class,
             entity: None,
+            muted: false,
+        });
+    }
+}
+
+/// Keeps the run of the live player in the ghost it took over once it completes the level
+pub fn commit_forked_records(
+    mut level_completed_event: EventReader<LevelCompletedEvent>,
+    mut forked_records: ResMut<ForkedRecords>,
+) {
+    if level_completed_event.read().last().is_some() {
+        forked_records.0 = None;
+    }
+}
+
+/// Gives the ghost taken over during an abandoned cycle its records back
+pub fn restore_forked_ghost(world: &mut World) {
+    let Some(stash) = world.resource_mut::<ForkedRecords>().0.take() else {
+        return;
+    };
+    let ghost = world.resource_mut::<PlayerGhostList>().forked.take();
+    world
+        .resource::<RecordedEvents>()
+        .clone()
+        .restore_ghost(world, stash);
+    if let Some(ghost) = ghost {
+        info!("Ghost {} got its records back", ghost.0 + 1);
+    }
+}
+
+/// Saves the run with F5 and loads it back with F9
+pub fn request_replay_file(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    current_level: Res<CurrentLevel>,
+    mut save_event: EventWriter<SaveReplayEvent>,
+    mut load_event: EventWriter<LoadReplayEvent>,
+) {
+    let Some(level) = &current_level.0 else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        save_event.send(SaveReplayEvent {
+            level: level.id,
+            path: replay_path(level.id),
+            player: None,
+        });
+    } else if keyboard_input.just_pressed(KeyCode::F9) {
+        load_event.send(LoadReplayEvent {
+            path: replay_path(level.id),
         });
     }
 }
+
+/// Saves the run that completed a level, the live player being its last ghost
+/// unless it took over one of the ghosts
+pub fn save_winning_run(
+    mut level_completed_event: EventReader<LevelCompletedEvent>,
+    player: Query<&Class, With<Player>>,
+    ghost_list: Res<PlayerGhostList>,
+    mut save_event: EventWriter<SaveReplayEvent>,
+) {
+    for e in level_completed_event.read() {
+        save_event.send(SaveReplayEvent {
+            level: e.level,
+            path: winning_replay_path(e.level),
+            player: player
+                .get_single()
+                .ok()
+                .copied()
+                .filter(|_| ghost_list.forked.is_none()),
+        });
+    }
+}
+
+/// Opens a replay file
+///
+/// Every recorded event type then writes its own section of a saved file,
+/// a loaded file is read as a whole when it is closed
+pub fn open_replay_file(
+    mut pending: ResMut<PendingReplay>,
+    mut save_event: EventReader<SaveReplayEvent>,
+    mut load_event: EventReader<LoadReplayEvent>,
+    current_level: Res<CurrentLevel>,
+    ghost_list: Res<PlayerGhostList>,
+) {
+    for e in save_event.read() {
+        let mut file = ReplayFile::new(e.level, &ghost_list);
+        file.ghosts.extend(e.player);
+        pending.0 = Some((ReplayMode::Save(e.path.clone()), file));
+    }
+
+    for e in load_event.read() {
+        let Some(level) = &current_level.0 else {
+            continue;
+        };
+        match ReplayFile::load(&e.path) {
+            Ok(file) if file.level == level.id => pending.0 = Some((ReplayMode::Load, file)),
+            Ok(file) => warn!("Replay file is for level {} not {}", file.level, level.id),
+            Err(e) => warn!("Could not load replay of level {}: {e}", level.id),
+        }
+    }
+}
+
+/// Writes the recorded events of the saved ghosts to the pending replay file
+pub fn save_replay_section<E: Event + ReplayCodec>(
+    mut pending: ResMut<PendingReplay>,
+    history: Res<LevelHistory<E>>,
+) {
+    if let Some((ReplayMode::Save(_), file)) = &mut pending.0 {
+        file.write_history(&history);
+    }
+}
+
+/// Writes the pending replay file to disk, or restores its ghosts and their histories
+///
+/// A loaded file replaces the ghosts only if every one of its sections can be read
+pub fn close_replay_file(world: &mut World) {
+    match world.resource_mut::<PendingReplay>().0.take() {
+        Some((ReplayMode::Save(path), file)) => match file.save(&path) {
+            Ok(()) => info!("Saved {} ghosts to {}", file.ghosts.len(), path.display()),
+            Err(e) => warn!("Could not save replay to {}: {e}", path.display()),
+        },
+        Some((ReplayMode::Load, file)) => match load_roster(world, &file) {
+            Ok(()) => info!(
+                "Loaded {} ghosts of level {}",
+                file.ghosts.len(),
+                file.level
+            ),
+            Err(e) => warn!("Could not load replay of level {}: {e}", file.level),
+        },
+        None => {}
+    }
+}
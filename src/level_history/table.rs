@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::data::*;
+use super::replay::*;
+use crate::player::prelude::*;
+use bevy::prelude::*;
+
+/// Events whose records can be exported to tables, and imported back from them
+///
+/// The payload of an event is a list of named numbers, booleans are 0 or 1.
+/// Like [`ReplayCodec`], the entity is not part of it
+pub trait RecordFields: Sized {
+    /// Names the event type in the `kind` column
+    const KIND: &'static str;
+    /// Names of the payload values, in the order of [`RecordFields::to_fields`]
+    const FIELDS: &'static [&'static str];
+    fn to_fields(&self) -> Vec<f32>;
+    /// Builds the event from its payload, missing values are 0
+    fn from_fields(fields: &[f32]) -> Self;
+}
+
+/// One record of a replay file, as a line of JSON or a row of CSV
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordRow {
+    pub level: usize,
+    pub ghost: usize,
+    pub class: String,
+    pub tick: u64,
+    /// Seconds since the start of the cycle, only written for the readers of the table
+    #[serde(default)]
+    pub time: f64,
+    pub kind: String,
+    #[serde(default)]
+    pub payload: BTreeMap<String, f32>,
+}
+
+/// Columns of the CSV tables before the payload values
+pub const CSV_COLUMNS: [&str; 6] = ["level", "ghost", "class", "tick", "time", "kind"];
+
+/// Kind of the rows that describe a ghost rather than one of its records,
+/// so a ghost without records and whether it is muted are kept
+pub const GHOST_KIND: &str = "ghost";
+
+/// Payload value of a ghost row, 1 when the ghost is muted
+pub const MUTED_FIELD: &str = "muted";
+
+/// One row per ghost of the file, at tick 0
+pub(super) fn ghost_rows(file: &ReplayFile) -> Vec<RecordRow> {
+    file.ghosts
+        .iter()
+        .enumerate()
+        .map(|(ghost, class)| RecordRow {
+            level: file.level,
+            ghost,
+            class: format!("{class:?}"),
+            tick: 0,
+            time: 0.0,
+            kind: GHOST_KIND.to_string(),
+            payload: BTreeMap::from([(
+                MUTED_FIELD.to_string(),
+                file.muted.contains(&ghost) as u8 as f32,
+            )]),
+        })
+        .collect()
+}
+
+pub(super) fn class_from_name(name: &str) -> Option<Class> {
+    [Class::Knight, Class::Ranger, Class::Wizard]
+        .into_iter()
+        .find(|class| format!("{class:?}").eq_ignore_ascii_case(name))
+}
+
+pub(super) fn history_to_rows<E: Event + ReplayCodec + RecordFields>(
+    file: &ReplayFile,
+    rows: &mut Vec<RecordRow>,
+) -> Result<(), ReplayError> {
+    for record in file.read_history::<E>()?.events() {
+        rows.push(RecordRow {
+            level: file.level,
+            ghost: record.ghost.0,
+            class: format!("{:?}", file.ghosts[record.ghost.0]),
+            tick: record.tick,
+            time: LevelTick(record.tick).as_secs(),
+            kind: E::KIND.to_string(),
+            payload: E::FIELDS
+                .iter()
+                .map(|name| name.to_string())
+                .zip(record.event.to_fields())
+                .collect(),
+        });
+    }
+    Ok(())
+}
+
+pub(super) fn history_from_rows<E: Event + ReplayCodec + RecordFields>(
+    file: &mut ReplayFile,
+    rows: &[RecordRow],
+) {
+    let mut history = LevelHistory::<E>::default();
+    for row in rows.iter().filter(|row| row.kind == E::KIND) {
+        let fields = E::FIELDS
+            .iter()
+            .map(|name| row.payload.get(*name).copied().unwrap_or(0.0))
+            .collect::<Vec<_>>();
+        history.push(EventRecord {
+            ghost: GhostIdentifier(row.ghost),
+            tick: row.tick,
+            event: E::from_fields(&fields),
+        });
+    }
+    file.write_history(&history);
+}
+
+/// Writes one JSON object per line
+pub fn write_jsonl(rows: &[RecordRow], mut writer: impl Write) -> Result<(), ReplayError> {
+    for row in rows {
+        serde_json::to_writer(&mut writer, row).map_err(|e| ReplayError::Table(e.to_string()))?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+pub fn read_jsonl(text: &str) -> Result<Vec<RecordRow>, ReplayError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| ReplayError::Table(format!("line {}: {e}", index + 1)))
+        })
+        .collect()
+}
+
+/// Writes a header and one row per record, payload values missing from a kind are left empty
+pub fn write_csv(
+    rows: &[RecordRow],
+    columns: &[&str],
+    mut writer: impl Write,
+) -> Result<(), ReplayError> {
+    writeln!(writer, "{}", columns.join(","))?;
+    for row in rows {
+        let values = columns
+            .iter()
+            .map(|column| match *column {
+                "level" => row.level.to_string(),
+                "ghost" => row.ghost.to_string(),
+                "class" => row.class.clone(),
+                "tick" => row.tick.to_string(),
+                "time" => format!("{:.4}", row.time),
+                "kind" => row.kind.clone(),
+                field => row
+                    .payload
+                    .get(field)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", values.join(","))?;
+    }
+    Ok(())
+}
+
+/// Reads a table written by [`write_csv`], columns may come in any order
+pub fn read_csv(text: &str) -> Result<Vec<RecordRow>, ReplayError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(vec![]);
+    };
+    let columns = header.split(',').map(str::trim).collect::<Vec<_>>();
+    for required in CSV_COLUMNS.iter().filter(|c| **c != "time") {
+        if !columns.contains(required) {
+            return Err(ReplayError::Table(format!("missing column {required}")));
+        }
+    }
+
+    lines
+        .map(|(index, line)| {
+            let error =
+                |message: String| ReplayError::Table(format!("line {}: {message}", index + 1));
+            let mut row = RecordRow {
+                level: 0,
+                ghost: 0,
+                class: String::new(),
+                tick: 0,
+                time: 0.0,
+                kind: String::new(),
+                payload: BTreeMap::new(),
+            };
+            for (column, value) in columns.iter().zip(line.split(',').map(str::trim)) {
+                let invalid = |_| error(format!("invalid {column} {value}"));
+                match *column {
+                    "level" => row.level = value.parse().map_err(invalid)?,
+                    "ghost" => row.ghost = value.parse().map_err(invalid)?,
+                    "class" => row.class = value.to_string(),
+                    "tick" => row.tick = value.parse().map_err(invalid)?,
+                    "time" => row.time = value.parse().unwrap_or_default(),
+                    "kind" => row.kind = value.to_string(),
+                    _ if value.is_empty() => {}
+                    field => {
+                        let value = value
+                            .parse()
+                            .map_err(|_| error(format!("invalid {field} {value}")))?;
+                        row.payload.insert(field.to_string(), value);
+                    }
+                }
+            }
+            Ok(row)
+        })
+        .collect()
+}
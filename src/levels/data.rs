@@ -1,6 +1,12 @@
+use std::fmt;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::ennemy::prelude::*;
 use crate::game::Level;
+use crate::player::prelude::*;
+use crate::walls::prelude::*;
 
 /// holds temporary if the button represents
 /// an unlocked level and which level it is
@@ -18,3 +24,79 @@ impl From<&Level> for LevelSelectionButton {
         }
     }
 }
+
+/// Where the campaign lists the files of the levels
+pub const CAMPAIGN_PATH: &str = "main.campaign.ron";
+
+/// A level as designers write it, in a `.level.ron` file
+#[derive(Asset, TypePath, Debug, Clone, Serialize, Deserialize)]
+pub struct LevelDefinition {
+    pub id: usize,
+    /// Size of the arena, centred on the origin and closed by walls
+    pub arena: Vec2,
+    /// Walls inside the arena, such as cover and corridors
+    #[serde(default)]
+    pub walls: Vec<WallShape>,
+    pub ennemies: Vec<EnnemyPlacement>,
+    /// Where the player and the ghosts start the cycles
+    pub spawn: Vec2,
+    /// Markers of the objectives, not used by the gameplay yet
+    #[serde(default)]
+    pub objectives: Vec<Vec2>,
+    /// Classes the player can pick
+    pub characters: Vec<Class>,
+    /// What unlocks this level, it is unlocked from the start without any
+    #[serde(default)]
+    pub requires: Vec<Prerequisite>,
+}
+
+/// A condition on the scores of the other levels to unlock a level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Prerequisite {
+    /// The level is completed
+    Completed(usize),
+    /// The level is completed in this number of cycles or less
+    Cycles { level: usize, at_most: usize },
+}
+
+impl Prerequisite {
+    pub fn is_met(&self, levels: &[Level]) -> bool {
+        let score = |id: usize| levels.iter().find(|l| l.id == id).and_then(|l| l.cycles);
+        match *self {
+            Prerequisite::Completed(level) => score(level).is_some(),
+            Prerequisite::Cycles { level, at_most } => {
+                score(level).is_some_and(|cycles| cycles <= at_most)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Prerequisite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Prerequisite::Completed(level) => write!(f, "Complete level {level}"),
+            Prerequisite::Cycles { level, at_most: 1 } => {
+                write!(f, "Complete level {level} in 1 cycle")
+            }
+            Prerequisite::Cycles { level, at_most } => {
+                write!(f, "Complete level {level} in {at_most} cycles or less")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnnemyPlacement {
+    pub kind: EnnemyKind,
+    pub position: Vec2,
+}
+
+/// The levels of the game, in a `.campaign.ron` file listing their paths
+#[derive(Asset, TypePath, Debug)]
+pub struct LevelCampaign {
+    pub levels: Vec<Handle<LevelDefinition>>,
+}
+
+/// Keeps the campaign loaded
+#[derive(Resource, Debug)]
+pub struct CampaignHandle(pub Handle<LevelCampaign>);
@@ -0,0 +1,7 @@
+use bevy::prelude::*;
+
+/// Sent when the file of a level was modified and its definition read again
+#[derive(Event, Debug, Clone)]
+pub struct LevelReloadedEvent {
+    pub level: usize,
+}
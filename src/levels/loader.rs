@@ -0,0 +1,90 @@
+use std::fmt;
+
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+
+use super::prelude::*;
+
+#[derive(Debug)]
+pub enum LevelLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for LevelLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelLoaderError::Io(e) => write!(f, "could not read the level file: {e}"),
+            LevelLoaderError::Ron(e) => write!(f, "invalid level file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelLoaderError {}
+
+impl From<std::io::Error> for LevelLoaderError {
+    fn from(value: std::io::Error) -> Self {
+        LevelLoaderError::Io(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for LevelLoaderError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        LevelLoaderError::Ron(value)
+    }
+}
+
+/// Reads the [`LevelDefinition`] of `.level.ron` files
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = LevelDefinition;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<LevelDefinition, LevelLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// Reads the list of level paths of `.campaign.ron` files, and loads the levels
+#[derive(Default)]
+pub struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+    type Asset = LevelCampaign;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<LevelCampaign, LevelLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let paths: Vec<String> = ron::de::from_bytes(&bytes)?;
+        Ok(LevelCampaign {
+            levels: paths
+                .into_iter()
+                .map(|path| load_context.load(path))
+                .collect(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.ron"]
+    }
+}
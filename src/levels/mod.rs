@@ -1,13 +1,43 @@
 mod data;
+mod events;
+mod loader;
 mod systems;
 
 use bevy::prelude::*;
+use data::*;
+use events::*;
+use loader::*;
 use systems::*;
 
 use crate::game::GameState;
 
 pub mod prelude {
     pub use super::data::*;
+    pub use super::events::*;
+    pub use super::LevelAssetPlugin;
+}
+
+/// Loads the levels of the campaign, then opens the level selection
+///
+/// Modified level files are applied while the game runs
+pub struct LevelAssetPlugin;
+
+impl Plugin for LevelAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LevelDefinition>()
+            .init_asset::<LevelCampaign>()
+            .init_asset_loader::<LevelLoader>()
+            .init_asset_loader::<CampaignLoader>()
+            .add_event::<LevelReloadedEvent>()
+            .add_systems(Startup, load_campaign)
+            .add_systems(
+                Update,
+                (
+                    finish_loading.run_if(in_state(GameState::Loading)),
+                    reload_levels,
+                ),
+            );
+    }
 }
 
 pub struct LevelsPlugin;
@@ -1,6 +1,7 @@
+use bevy::asset::RecursiveDependencyLoadState;
 use bevy::{color::palettes::tailwind, prelude::*};
 
-use crate::game::{CurrentLevel, GameState, Levels};
+use crate::game::{CurrentLevel, GameState, Level, Levels};
 
 use super::prelude::*;
 
@@ -50,6 +51,7 @@ pub fn spawn_level_selection(
                                 flex_direction: FlexDirection::Column,
                                 align_items: AlignItems::Center,
                                 justify_content: JustifyContent::Center,
+                                padding: UiRect::all(Val::Px(10.0)),
                                 ..default()
                             },
                             ..default()
@@ -65,6 +67,31 @@ pub fn spawn_level_selection(
                                 ..default()
                             },
                         ));
+                        if !level.unlocked {
+                            // What is left to do to unlock the level
+                            selector.spawn(TextBundle::from_section(
+                                "Locked",
+                                TextStyle {
+                                    font: asset_server.load("Kalam-Regular.ttf"),
+                                    font_size: 32.0,
+                                    ..default()
+                                },
+                            ));
+                            for prerequisite in levels.missing(level) {
+                                selector.spawn(
+                                    TextBundle::from_section(
+                                        prerequisite.to_string(),
+                                        TextStyle {
+                                            font: asset_server.load("Kalam-Light.ttf"),
+                                            font_size: 18.0,
+                                            color: tailwind::STONE_300.into(),
+                                        },
+                                    )
+                                    .with_text_justify(JustifyText::Center),
+                                );
+                            }
+                            return;
+                        }
                         let score: String;
                         if let Some(existing_score) = level.cycles {
                             score = existing_score.to_string();
@@ -106,3 +133,76 @@ pub fn interaction_on_level_selection_buttons(
         }
     }
 }
+
+pub fn load_campaign(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CampaignHandle(asset_server.load(CAMPAIGN_PATH)));
+}
+
+/// Fills [`Levels`] from the campaign once it is loaded with all its levels
+///
+/// A campaign that failed to load leaves no level to play
+pub fn finish_loading(
+    asset_server: Res<AssetServer>,
+    campaign: Res<CampaignHandle>,
+    campaigns: Res<Assets<LevelCampaign>>,
+    definitions: Res<Assets<LevelDefinition>>,
+    mut levels: ResMut<Levels>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    match asset_server.get_recursive_dependency_load_state(&campaign.0) {
+        Some(RecursiveDependencyLoadState::Loaded) => {}
+        Some(RecursiveDependencyLoadState::Failed) => {
+            error!("Could not load the levels of {CAMPAIGN_PATH}");
+            game_state.set(GameState::LevelSelection);
+            return;
+        }
+        _ => return,
+    }
+
+    levels.0 = campaigns
+        .get(&campaign.0)
+        .map(|c| c.levels.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|handle| {
+            let definition = definitions.get(handle)?;
+            Some(Level::new(definition, handle.clone()))
+        })
+        .collect();
+    levels.sort_by_key(|l| l.id);
+    info!("Loaded {} levels", levels.len());
+    game_state.set(GameState::LevelSelection);
+}
+
+/// Applies the modified level files to the levels of the campaign
+///
+/// Files are only watched with the `file_watcher` feature of Bevy, as `just run` does
+pub fn reload_levels(
+    mut asset_events: EventReader<AssetEvent<LevelDefinition>>,
+    definitions: Res<Assets<LevelDefinition>>,
+    mut levels: ResMut<Levels>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut reloaded_event: EventWriter<LevelReloadedEvent>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(definition) = definitions.get(*id) else {
+            continue;
+        };
+        // A change of the current level would bring back its stored ghosts over the ones in play
+        for level in levels
+            .iter_mut()
+            .chain(current_level.bypass_change_detection().0.iter_mut())
+            .filter(|l| l.definition.id() == *id)
+        {
+            level.apply(definition);
+        }
+        levels.unlock_available();
+        info!("Reloaded level {}", definition.id);
+        reloaded_event.send(LevelReloadedEvent {
+            level: definition.id,
+        });
+    }
+}
@@ -0,0 +1,51 @@
+// Bevy code commonly triggers these lints and they may be important signals
+// about code quality. They are sometimes hard to avoid though, and the CI
+// workflow treats them as errors, so this allows them throughout the project.
+// Feel free to delete this line.
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod character;
+pub mod editor;
+pub mod ennemy;
+pub mod game;
+pub mod level_history;
+pub mod levels;
+pub mod pause;
+pub mod playback;
+pub mod player;
+pub mod timeline;
+pub mod verification;
+pub mod walls;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use ennemy::prelude::*;
+use level_history::prelude::*;
+use levels::prelude::*;
+use player::prelude::*;
+use walls::prelude::*;
+
+/// The physics and the gameplay of the levels
+///
+/// It does not render, read input devices nor spawn menus,
+/// so it is shared by the game and the headless replay verification
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Disabling gravity
+            .insert_resource(Gravity(Vec2::ZERO))
+            // Setting global timer for physics update,
+            // physics steps exactly once per fixed update so ticks stay in sync with the level history
+            .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .insert_resource(Time::new_with(Physics::fixed_once_hz(TICK_RATE)))
+            .add_plugins(PhysicsPlugins::new(FixedPostUpdate).with_length_unit(PLAYER_RADIUS))
+            .add_plugins(PlayerPlugin)
+            .add_plugins(EnnemyPlugin)
+            .add_plugins(WallPlugin)
+            .add_plugins(game::GamePlugin)
+            .add_plugins(LevelAssetPlugin)
+            .add_plugins(LevelHistoryPlugin);
+    }
+}
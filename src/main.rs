@@ -1,33 +1,18 @@
-// Bevy code commonly triggers these lints and they may be important signals
-// about code quality. They are sometimes hard to avoid though, and the CI
-// workflow treats them as errors, so this allows them throughout the project.
-// Feel free to delete this line.
-#![allow(clippy::too_many_arguments, clippy::type_complexity)]
-
-mod character;
-mod ennemy;
-mod game;
-mod level_history;
-mod levels;
-mod player;
-mod walls;
-
-use avian2d::prelude::*;
 use bevy::asset::AssetMetaCheck;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
-use character::CharactersPlugin;
-use ennemy::prelude::*;
-use level_history::prelude::*;
-use player::prelude::*;
-use walls::prelude::*;
+use cycle_of_the_fallen::character::CharactersPlugin;
+use cycle_of_the_fallen::editor::prelude::*;
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::levels;
+use cycle_of_the_fallen::pause::prelude::*;
+use cycle_of_the_fallen::playback::prelude::*;
+use cycle_of_the_fallen::player::prelude::*;
+use cycle_of_the_fallen::timeline::prelude::*;
+use cycle_of_the_fallen::SimulationPlugin;
 
 fn main() {
     App::new()
-        // Disabling gravity
-        .insert_resource(Gravity(Vec2::ZERO))
-        // Setting global timer for physics update
-        .insert_resource(Time::new_with(Physics::fixed_hz(144.0)))
         .add_plugins((
             DefaultPlugins
                 .set(AssetPlugin {
@@ -41,19 +26,23 @@ fn main() {
                     filter: "info,cycle_of_the_fallen=debug".to_string(),
                     ..default()
                 }),
-            PhysicsPlugins::default().with_length_unit(PLAYER_RADIUS),
+            SimulationPlugin,
         ))
-        .add_plugins(PlayerPlugin)
-        .add_plugins(EnnemyPlugin)
-        .add_plugins(WallPlugin)
+        .add_plugins(PlayerControlPlugin)
         .add_plugins(levels::LevelsPlugin)
-        .add_plugins(game::GamePlugin)
         .add_plugins(CharactersPlugin)
-        .add_plugins(LevelHistoryPlugin)
+        .add_plugins(ReplayFilePlugin)
+        .add_plugins(TimelinePlugin)
+        .add_plugins(PlaybackPlugin)
+        .add_plugins(PausePlugin)
+        .add_plugins(EditorPlugin)
         .add_systems(Startup, setup)
         .run();
 }
 
 fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
+    if std::env::args().any(|arg| arg == "--save-winning-runs") {
+        commands.insert_resource(SaveWinningRuns);
+    }
 }
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+
+/// Root node of the pause menu
+#[derive(Component)]
+pub struct PauseMenu;
+
+/// What a button of the pause menu does when pressed
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseButton {
+    Resume,
+    /// Plays the cycle again from its start, see [`RestartCycleEvent`](crate::timeline::prelude::RestartCycleEvent)
+    RestartCycle,
+    /// Goes back to the level selection, the cycle is not recorded
+    AbandonLevel,
+    Settings,
+    /// Switches between snapping and smoothing the ghosts onto their keyframes
+    Correction,
+    /// Goes to the next speed of the cycles, back to the slowest after the fastest
+    Speed,
+    Back,
+}
+
+/// The part of the pause menu that is shown
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuPage {
+    #[default]
+    Actions,
+    Settings,
+}
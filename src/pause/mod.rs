@@ -0,0 +1,41 @@
+mod data;
+mod systems;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use data::*;
+use systems::*;
+
+use crate::game::{GameState, PlayState};
+use crate::timeline::prelude::{FastForward, Scrubbing};
+
+pub mod prelude {
+    pub use super::data::*;
+    pub use super::PausePlugin;
+}
+
+/// Pauses the cycle with Escape or when the window loses the focus, and shows the pause menu
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseMenuPage>()
+            .add_systems(OnEnter(PlayState::Paused), (pause_clocks, open_pause_menu))
+            .add_systems(OnExit(PlayState::Paused), resume_clocks)
+            // Before the timeline reads Escape, which also stops scrubbing
+            .add_systems(
+                PreUpdate,
+                (toggle_pause, pause_on_focus_lost)
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::Play))
+                    .run_if(not(resource_exists::<Scrubbing>))
+                    .run_if(not(resource_exists::<FastForward>)),
+            )
+            .add_systems(
+                Update,
+                (interaction_on_pause_buttons, spawn_pause_menu)
+                    .chain()
+                    .run_if(in_state(PlayState::Paused)),
+            );
+    }
+}
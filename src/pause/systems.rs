@@ -0,0 +1,195 @@
+use bevy::window::WindowFocused;
+use bevy::{color::palettes::tailwind, prelude::*};
+
+use super::prelude::*;
+use crate::game::{GameState, PlayState};
+use crate::level_history::prelude::*;
+use crate::timeline::prelude::*;
+
+/// Pauses or resumes the cycle with Escape
+pub fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    play_state: Res<State<PlayState>>,
+    mut next_state: ResMut<NextState<PlayState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    next_state.set(match play_state.get() {
+        PlayState::Running => PlayState::Paused,
+        PlayState::Paused => PlayState::Running,
+    });
+}
+
+/// Pauses the cycle when the player switches to another window
+pub fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    play_state: Res<State<PlayState>>,
+    mut next_state: ResMut<NextState<PlayState>>,
+) {
+    if focus_events.read().any(|e| !e.focused) && play_state.get() == &PlayState::Running {
+        next_state.set(PlayState::Paused);
+    }
+}
+
+/// Stops the virtual clock, so no fixed tick runs:
+/// the physics, the ennemies, the level tick and the replay all wait
+pub fn pause_clocks(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn resume_clocks(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+pub fn open_pause_menu(mut page: ResMut<PauseMenuPage>) {
+    *page = PauseMenuPage::Actions;
+}
+
+/// Spawns the page of the pause menu, it is rebuilt when the page or a setting changes
+pub fn spawn_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    page: Res<PauseMenuPage>,
+    correction: Res<KeyframeCorrection>,
+    time_scale: Res<TimeScale>,
+    menus: Query<Entity, With<PauseMenu>>,
+) {
+    if !menus.is_empty()
+        && !page.is_changed()
+        && !correction.is_changed()
+        && !time_scale.is_changed()
+    {
+        return;
+    }
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let (title, buttons) = match *page {
+        PauseMenuPage::Actions => (
+            "Paused",
+            vec![
+                (PauseButton::Resume, "Resume".to_string()),
+                (PauseButton::RestartCycle, "Restart cycle".to_string()),
+                (PauseButton::AbandonLevel, "Abandon level".to_string()),
+                (PauseButton::Settings, "Settings".to_string()),
+            ],
+        ),
+        PauseMenuPage::Settings => {
+            let correction = match *correction {
+                KeyframeCorrection::Snap => "Snap".to_string(),
+                KeyframeCorrection::Smooth { ticks } => format!("Smooth over {ticks} ticks"),
+            };
+            (
+                "Settings",
+                vec![
+                    (
+                        PauseButton::Correction,
+                        format!("Ghost correction: {correction}"),
+                    ),
+                    (
+                        PauseButton::Speed,
+                        format!("Speed: x{}", time_scale.speed()),
+                    ),
+                    (PauseButton::Back, "Back".to_string()),
+                ],
+            )
+        }
+    };
+
+    commands
+        .spawn((
+            PauseMenu,
+            StateScoped(PlayState::Paused),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Vw(100.0),
+                    height: Val::Vh(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+        ))
+        .with_children(|menu| {
+            menu.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: asset_server.load("Kalam-Light.ttf"),
+                    font_size: 80.0,
+                    ..default()
+                },
+            ));
+            for (button, label) in buttons {
+                menu.spawn((
+                    button,
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(360.0),
+                            padding: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: tailwind::LIME_800.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font: asset_server.load("Kalam-Bold.ttf"),
+                            font_size: 28.0,
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+pub fn interaction_on_pause_buttons(
+    mut query: Query<(&Interaction, &mut BackgroundColor, &PauseButton), Changed<Interaction>>,
+    mut page: ResMut<PauseMenuPage>,
+    mut correction: ResMut<KeyframeCorrection>,
+    mut time_scale: ResMut<TimeScale>,
+    mut play_state: ResMut<NextState<PlayState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut restart_event: EventWriter<RestartCycleEvent>,
+) {
+    for (interaction, mut background_color, button) in query.iter_mut() {
+        *background_color = match *interaction {
+            Interaction::Pressed => {
+                match button {
+                    PauseButton::Resume => play_state.set(PlayState::Running),
+                    PauseButton::RestartCycle => {
+                        restart_event.send(RestartCycleEvent);
+                        play_state.set(PlayState::Running);
+                    }
+                    PauseButton::AbandonLevel => game_state.set(GameState::LevelSelection),
+                    PauseButton::Settings => *page = PauseMenuPage::Settings,
+                    PauseButton::Back => *page = PauseMenuPage::Actions,
+                    PauseButton::Correction => {
+                        *correction = match *correction {
+                            KeyframeCorrection::Snap => KeyframeCorrection::default(),
+                            KeyframeCorrection::Smooth { .. } => KeyframeCorrection::Snap,
+                        }
+                    }
+                    PauseButton::Speed => {
+                        time_scale.0 = (time_scale.0 + 1) % TIME_SCALES.len();
+                    }
+                }
+                tailwind::LIME_300.into()
+            }
+            Interaction::Hovered => tailwind::LIME_500.into(),
+            Interaction::None => tailwind::LIME_800.into(),
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ennemy::prelude::*;
+use crate::level_history::prelude::*;
+use crate::player::prelude::*;
+use crate::timeline::prelude::*;
+use bevy::prelude::*;
+
+/// Ticks shown by the kill cam before the death of the player
+pub const KILL_CAM_TICKS: u64 = 3 * TICK_RATE as u64;
+
+/// Ticks between two keyframes of a recorded cycle, the ticks in between
+/// only hold what changed since the tick before
+pub const CYCLE_KEYFRAME_INTERVAL: u64 = TICK_RATE as u64;
+
+/// Present while the cycles are recorded to be watched back, toggled with F7
+/// in the level selection
+#[derive(Resource, Debug, Default)]
+pub struct CycleRecorder;
+
+/// Whoever shot a projectile
+#[derive(Debug, Clone, Copy)]
+pub enum Shooter {
+    Player,
+    Ghost(GhostIdentifier),
+    Ennemy(EnnemyKind),
+}
+
+/// Something that happened during a recorded cycle
+#[derive(Debug, Clone)]
+pub enum CycleEvent {
+    Attack {
+        shooter: Shooter,
+        position: Vec2,
+        velocity: Vec2,
+    },
+    EnnemyKilled {
+        kind: EnnemyKind,
+    },
+    GhostKilled {
+        ghost: GhostIdentifier,
+        killer: Killer,
+        position: Vec2,
+    },
+    PlayerKilled {
+        position: Vec2,
+    },
+}
+
+impl CycleEvent {
+    /// Where the event happened, if it happened somewhere
+    pub fn position(&self) -> Option<Vec2> {
+        match self {
+            CycleEvent::Attack { position, .. }
+            | CycleEvent::GhostKilled { position, .. }
+            | CycleEvent::PlayerKilled { position } => Some(*position),
+            CycleEvent::EnnemyKilled { .. } => None,
+        }
+    }
+}
+
+/// A wall of a recorded cycle
+#[derive(Debug, Clone)]
+pub struct WallSnapshot {
+    /// Closed, in the world
+    pub outline: Vec<Vec2>,
+}
+
+/// Everything that happened during one cycle
+///
+/// Every body of the level is recorded at every tick, so a cycle is watched back
+/// tick for tick as it was played, whatever the ghosts replayed.
+/// The bodies of a tick are encoded as what changed since the tick before,
+/// and every [`CYCLE_KEYFRAME_INTERVAL`] ticks as a whole, to read any tick quickly
+#[derive(Debug, Clone, Default)]
+pub struct CycleRecording {
+    pub level: usize,
+    pub walls: Vec<WallSnapshot>,
+    /// The encoded bodies of every recorded tick, one after the other
+    bytes: Vec<u8>,
+    /// Where each keyframe starts in `bytes`
+    keyframes: Vec<usize>,
+    /// Every kind of body met, the ticks name them by index
+    kinds: Vec<BodyKind>,
+    /// The bodies of the last recorded tick
+    last: Vec<BodySnapshot>,
+    /// Number of recorded ticks
+    ticks: u64,
+    /// Sorted by tick
+    pub events: Vec<(u64, CycleEvent)>,
+    pub completed: bool,
+}
+
+/// What changed in a body since the tick before
+const BODY_KIND: u8 = 1;
+const BODY_POSITION: u8 = 2;
+const BODY_ROTATION: u8 = 4;
+
+impl CycleRecording {
+    pub fn new(level: usize) -> Self {
+        Self { level, ..default() }
+    }
+
+    /// Number of recorded ticks
+    pub fn len(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks == 0
+    }
+
+    /// Records the bodies of the tick following the last recorded one
+    pub fn record_tick(&mut self, tick: u64, bodies: Vec<BodySnapshot>) {
+        debug_assert_eq!(tick, self.ticks, "the ticks are recorded in order");
+        if tick % CYCLE_KEYFRAME_INTERVAL == 0 {
+            self.keyframes.push(self.bytes.len());
+            self.last.clear();
+        }
+        let mut writer = ReplayWriter(std::mem::take(&mut self.bytes));
+        writer.varint(bodies.len() as u64);
+        for (index, body) in bodies.iter().enumerate() {
+            let before = self.last.get(index);
+            let mut changes = 0;
+            if before.map_or(true, |b| b.kind != body.kind) {
+                changes |= BODY_KIND;
+            }
+            if before.map_or(true, |b| b.position != body.position) {
+                changes |= BODY_POSITION;
+            }
+            if before.map_or(true, |b| b.rotation != body.rotation) {
+                changes |= BODY_ROTATION;
+            }
+            writer.u8(changes);
+            if changes & BODY_KIND != 0 {
+                let kind = match self.kinds.iter().position(|k| *k == body.kind) {
+                    Some(kind) => kind,
+                    None => {
+                        self.kinds.push(body.kind);
+                        self.kinds.len() - 1
+                    }
+                };
+                writer.varint(kind as u64);
+            }
+            if changes & BODY_POSITION != 0 {
+                writer.f32(body.position.x);
+                writer.f32(body.position.y);
+            }
+            if changes & BODY_ROTATION != 0 {
+                for value in body.rotation.to_array() {
+                    writer.f32(value);
+                }
+            }
+        }
+        self.bytes = writer.0;
+        self.last = bodies;
+        self.ticks = tick + 1;
+    }
+
+    /// Decodes the tick at `reader` over the bodies of the tick before
+    fn read_tick(&self, reader: &mut ReplayReader, bodies: &mut Vec<BodySnapshot>) {
+        let read = |reader: &mut ReplayReader, bodies: &mut Vec<BodySnapshot>| {
+            let count = reader.varint()? as usize;
+            bodies.truncate(count);
+            for index in 0..count {
+                let changes = reader.u8()?;
+                let before = bodies.get(index);
+                let kind = match before {
+                    Some(body) if changes & BODY_KIND == 0 => body.kind,
+                    _ => self.kinds[reader.varint()? as usize],
+                };
+                let position = match before {
+                    Some(body) if changes & BODY_POSITION == 0 => body.position,
+                    _ => Vec2::new(reader.f32()?, reader.f32()?),
+                };
+                let rotation = match before {
+                    Some(body) if changes & BODY_ROTATION == 0 => body.rotation,
+                    _ => {
+                        Quat::from_xyzw(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?)
+                    }
+                };
+                let body = BodySnapshot {
+                    kind,
+                    position,
+                    rotation,
+                };
+                match bodies.get_mut(index) {
+                    Some(before) => *before = body,
+                    None => bodies.push(body),
+                }
+            }
+            Ok::<_, ReplayError>(())
+        };
+        read(reader, bodies).expect("ticks are read back as they were written");
+    }
+
+    /// The bodies at `tick`, and where the tick after it starts
+    fn read_until(&self, tick: u64) -> (Vec<BodySnapshot>, usize) {
+        let keyframe = tick / CYCLE_KEYFRAME_INTERVAL;
+        let start = self.keyframes[keyframe as usize];
+        let mut reader = ReplayReader(&self.bytes[start..]);
+        let mut bodies = vec![];
+        for _ in keyframe * CYCLE_KEYFRAME_INTERVAL..=tick {
+            self.read_tick(&mut reader, &mut bodies);
+        }
+        (bodies, self.bytes.len() - reader.0.len())
+    }
+
+    /// Every body of the level at `tick`, as it was recorded
+    pub fn frame(&self, tick: u64) -> Option<Vec<BodySnapshot>> {
+        (tick < self.ticks).then(|| self.read_until(tick).0)
+    }
+
+    /// Forgets what happened from `tick` onward
+    pub fn truncate(&mut self, tick: u64) {
+        if tick >= self.ticks {
+            return;
+        }
+        if tick == 0 {
+            self.bytes.clear();
+            self.keyframes.clear();
+            self.last.clear();
+        } else {
+            let (last, end) = self.read_until(tick - 1);
+            self.bytes.truncate(end);
+            self.keyframes
+                .truncate(((tick - 1) / CYCLE_KEYFRAME_INTERVAL + 1) as usize);
+            self.last = last;
+        }
+        self.ticks = tick;
+        let index = self.events.partition_point(|(t, _)| *t < tick);
+        self.events.truncate(index);
+    }
+
+    /// The tick the kill cam starts at, a few seconds before the player died
+    pub fn kill_cam_start(&self) -> u64 {
+        let death = self
+            .events
+            .iter()
+            .rev()
+            .find(|(_, e)| matches!(e, CycleEvent::PlayerKilled { .. }))
+            .map(|(tick, _)| *tick)
+            .unwrap_or(self.len());
+        death.saturating_sub(KILL_CAM_TICKS)
+    }
+
+    /// Memory held by the recording, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.bytes.capacity()
+            + self.keyframes.capacity() * std::mem::size_of::<usize>()
+            + self.kinds.capacity() * std::mem::size_of::<BodyKind>()
+            + self.last.capacity() * std::mem::size_of::<BodySnapshot>()
+            + self.events.capacity() * std::mem::size_of::<(u64, CycleEvent)>()
+            + self
+                .walls
+                .iter()
+                .map(|wall| wall.outline.capacity() * std::mem::size_of::<Vec2>())
+                .sum::<usize>()
+    }
+}
+
+/// The cycle being played, once it ends it is kept by [`CycleRecordings`]
+#[derive(Resource, Debug, Default)]
+pub struct CurrentCycle(pub CycleRecording);
+
+/// The finished cycles that can be watched, by level id
+///
+/// The last cycle of a level is often its winning one, they share the recording
+#[derive(Resource, Debug, Default)]
+pub struct CycleRecordings {
+    pub last: HashMap<usize, Arc<CycleRecording>>,
+    pub winning: HashMap<usize, Arc<CycleRecording>>,
+}
+
+impl CycleRecordings {
+    /// Memory held by the recordings, in bytes
+    pub fn memory_usage(&self) -> usize {
+        let mut recordings = self.last.values().collect::<Vec<_>>();
+        for recording in self.winning.values() {
+            if !recordings.iter().any(|r| Arc::ptr_eq(r, recording)) {
+                recordings.push(recording);
+            }
+        }
+        recordings.iter().map(|r| r.memory_usage()).sum()
+    }
+}
+
+/// A recorded cycle being watched
+#[derive(Resource, Debug)]
+pub struct Playback {
+    pub recording: Arc<CycleRecording>,
+    /// The moment shown, in ticks
+    pub tick: f64,
+    pub speed: f64,
+    pub paused: bool,
+}
+
+/// Which recorded cycle of a level to watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackKind {
+    /// The last cycle that completed the level, from its start
+    WinningRun,
+    /// The end of the last cycle, slowed down
+    KillCam,
+}
+
+/// A button of the level selection that starts a playback
+#[derive(Component)]
+pub struct WatchButton {
+    pub level: usize,
+    pub kind: PlaybackKind,
+}
+
+/// Marker of the text telling whether the cycles are recorded
+#[derive(Component)]
+pub struct RecorderText;
+
+/// Marker of the text describing the watched moment
+#[derive(Component)]
+pub struct PlaybackText;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bodies of a tick, some move at every tick and the projectiles come and go
+    fn bodies(tick: u64) -> Vec<BodySnapshot> {
+        let mut bodies = vec![
+            BodySnapshot {
+                kind: BodyKind::Player,
+                position: Vec2::new(tick as f32 * 0.37, 10.0),
+                rotation: Quat::from_rotation_z(tick as f32 * 0.01),
+            },
+            BodySnapshot {
+                kind: BodyKind::Ennemy(EnnemyKind::Turret),
+                position: Vec2::new(-50.0, (tick / 30) as f32),
+                rotation: Quat::IDENTITY,
+            },
+        ];
+        for index in 0..tick % 7 {
+            bodies.push(BodySnapshot {
+                kind: BodyKind::Projectile(Team::Enemy),
+                position: Vec2::new(index as f32, tick as f32),
+                rotation: Quat::IDENTITY,
+            });
+        }
+        bodies
+    }
+
+    fn record(recording: &mut CycleRecording, ticks: std::ops::Range<u64>, offset: u64) {
+        for tick in ticks {
+            recording.truncate(tick);
+            recording.record_tick(tick, bodies(tick + offset));
+        }
+    }
+
+    #[test]
+    fn every_tick_is_kept() {
+        let mut recording = CycleRecording::default();
+        let ticks = CYCLE_KEYFRAME_INTERVAL * 5 + 3;
+        record(&mut recording, 0..ticks, 0);
+        assert_eq!(recording.len(), ticks);
+        for tick in 0..ticks {
+            assert_eq!(recording.frame(tick), Some(bodies(tick)));
+        }
+        assert_eq!(recording.frame(ticks), None);
+        // What did not move since the tick before takes a byte
+        assert!(
+            recording.memory_usage() < ticks as usize * 3 * std::mem::size_of::<BodySnapshot>()
+        );
+    }
+
+    #[test]
+    fn a_seek_records_again_from_its_tick() {
+        let mut recording = CycleRecording::default();
+        let ticks = CYCLE_KEYFRAME_INTERVAL * 3;
+        record(&mut recording, 0..ticks, 0);
+
+        // Within a keyframe interval, and right at a keyframe
+        for seek in [CYCLE_KEYFRAME_INTERVAL + 20, CYCLE_KEYFRAME_INTERVAL] {
+            recording.truncate(seek);
+            assert_eq!(recording.len(), seek);
+            record(&mut recording, seek..ticks, 1000);
+            for tick in 0..seek {
+                assert_eq!(recording.frame(tick), Some(bodies(tick)));
+            }
+            for tick in seek..ticks {
+                assert_eq!(recording.frame(tick), Some(bodies(tick + 1000)));
+            }
+            record(&mut recording, seek..ticks, 0);
+        }
+    }
+}
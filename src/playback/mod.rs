@@ -0,0 +1,57 @@
+mod data;
+mod systems;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use data::*;
+use systems::*;
+
+use crate::game::GameState;
+
+pub mod prelude {
+    pub use super::data::*;
+    pub use super::PlaybackPlugin;
+}
+
+/// Records the cycles while [`CycleRecorder`] is present and lets the player
+/// watch them back from the level selection, as the winning run or as a kill cam
+pub struct PlaybackPlugin;
+
+impl Plugin for PlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentCycle>()
+            .init_resource::<CycleRecordings>()
+            .add_systems(
+                OnEnter(GameState::Play),
+                start_cycle_recording.run_if(resource_exists::<CycleRecorder>),
+            )
+            .add_systems(OnExit(GameState::Play), end_cycle_recording)
+            // Recorded once the physics stepped, so the kills of the tick are in it
+            .add_systems(
+                FixedPostUpdate,
+                record_cycle
+                    .after(PhysicsSet::Sync)
+                    .run_if(in_state(GameState::Play))
+                    .run_if(resource_exists::<CycleRecorder>),
+            )
+            .add_systems(OnEnter(GameState::LevelSelection), spawn_watch_buttons)
+            .add_systems(
+                Update,
+                (interaction_on_watch_buttons, toggle_cycle_recorder)
+                    .run_if(in_state(GameState::LevelSelection)),
+            )
+            .add_systems(OnEnter(GameState::Watch), spawn_playback_text)
+            .add_systems(OnExit(GameState::Watch), stop_playback)
+            .add_systems(
+                Update,
+                (
+                    control_playback,
+                    advance_playback,
+                    draw_playback,
+                    update_playback_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Watch)),
+            );
+    }
+}
@@ -0,0 +1,463 @@
+use std::sync::Arc;
+
+use avian2d::prelude::*;
+use bevy::{color::palettes::tailwind, prelude::*};
+
+use super::prelude::*;
+use crate::ennemy::prelude::*;
+use crate::game::{CurrentLevel, GameState, LevelCompletedEvent};
+use crate::level_history::prelude::*;
+use crate::player::prelude::*;
+use crate::timeline::prelude::*;
+use crate::timeline::{draw_bodies, snapshot_bodies, SnapshotBodies};
+use crate::walls::prelude::*;
+
+/// Starts recording the cycle of the current level
+pub fn start_cycle_recording(
+    mut current_cycle: ResMut<CurrentCycle>,
+    current_level: Res<CurrentLevel>,
+) {
+    current_cycle.0 =
+        CycleRecording::new(current_level.0.as_ref().map(|l| l.id).unwrap_or_default());
+}
+
+/// Records what happened at the end of a tick, and where every body of the level is
+///
+/// After a seek the cycle is simulated again from its start,
+/// so what was recorded from the current tick onward is replaced
+pub fn record_cycle(
+    tick: Res<LevelTick>,
+    mut current_cycle: ResMut<CurrentCycle>,
+    bodies: SnapshotBodies,
+    walls: Query<(&Transform, &Collider), With<Wall>>,
+    projectiles: Query<(&AttackProjectile, &Transform, &LinearVelocity), Added<AttackProjectile>>,
+    shooters: Query<(Has<Player>, Option<&GhostIdentifier>, Option<&EnnemyKind>)>,
+    players: Query<&Transform, With<Player>>,
+    mut ennemy_killed_event: EventReader<EnnemyKilledEvent>,
+    mut ghost_killed_event: EventReader<GhostKilledEvent>,
+    mut player_killed_event: EventReader<PlayerKilledEvent>,
+) {
+    // The level tick is advanced before the physics steps
+    let tick = tick.0.saturating_sub(1);
+    let recording = &mut current_cycle.0;
+    recording.truncate(tick);
+
+    // The walls are spawned with the level, they are taken once they exist
+    if recording.walls.is_empty() {
+        recording.walls = walls
+            .iter()
+            .map(|(transform, collider)| WallSnapshot {
+                outline: collider_outline(
+                    collider,
+                    transform.translation.truncate(),
+                    transform.rotation.to_euler(EulerRot::XYZ).2,
+                ),
+            })
+            .collect();
+    }
+
+    let mut events = vec![];
+    for (projectile, transform, velocity) in projectiles.iter() {
+        let shooter = match shooters.get(projectile.shooter) {
+            Ok((true, _, _)) => Shooter::Player,
+            Ok((_, Some(ghost), _)) => Shooter::Ghost(*ghost),
+            Ok((_, _, Some(kind))) => Shooter::Ennemy(*kind),
+            _ => continue,
+        };
+        events.push(CycleEvent::Attack {
+            shooter,
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+        });
+    }
+    for e in ennemy_killed_event.read() {
+        events.push(CycleEvent::EnnemyKilled { kind: e.kind });
+    }
+    for e in ghost_killed_event.read() {
+        if e.source != EventSource::Input {
+            continue;
+        }
+        events.push(CycleEvent::GhostKilled {
+            ghost: e.ghost,
+            killer: e.killer,
+            position: e.position,
+        });
+    }
+    for e in player_killed_event.read() {
+        if e.source != EventSource::Input {
+            continue;
+        }
+        if let Ok(transform) = players.get(e.entity) {
+            events.push(CycleEvent::PlayerKilled {
+                position: transform.translation.truncate(),
+            });
+        }
+    }
+
+    recording
+        .events
+        .extend(events.into_iter().map(|event| (tick, event)));
+    recording.record_tick(tick, snapshot_bodies(&bodies));
+}
+
+/// Keeps the cycle that ended, and the last one that completed the level
+pub fn end_cycle_recording(
+    mut current_cycle: ResMut<CurrentCycle>,
+    mut recordings: ResMut<CycleRecordings>,
+    mut level_completed_event: EventReader<LevelCompletedEvent>,
+) {
+    let mut recording = std::mem::take(&mut current_cycle.0);
+    if recording.is_empty() {
+        return;
+    }
+    recording.completed = level_completed_event
+        .read()
+        .any(|e| e.level == recording.level);
+    info!(
+        "Recorded {} ticks of level {} in {:.1} KiB",
+        recording.len(),
+        recording.level,
+        recording.memory_usage() as f64 / 1024.0
+    );
+    let recording = Arc::new(recording);
+    if recording.completed {
+        recordings
+            .winning
+            .insert(recording.level, Arc::clone(&recording));
+    }
+    recordings.last.insert(recording.level, recording);
+}
+
+fn recorder_label(recorder: bool, recordings: &CycleRecordings) -> String {
+    if recorder {
+        format!(
+            "F7: stop recording the cycles ({:.1} KiB held)",
+            recordings.memory_usage() as f64 / 1024.0
+        )
+    } else {
+        "F7: record the cycles to watch them back".to_string()
+    }
+}
+
+/// Turns the recording of the cycles on and off with F7
+pub fn toggle_cycle_recorder(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    recorder: Option<Res<CycleRecorder>>,
+    recordings: Res<CycleRecordings>,
+    mut text: Query<&mut Text, With<RecorderText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    if recorder.is_some() {
+        commands.remove_resource::<CycleRecorder>();
+    } else {
+        commands.insert_resource(CycleRecorder);
+    }
+    info!("Recording the cycles: {}", recorder.is_none());
+    for mut text in text.iter_mut() {
+        text.sections[0].value = recorder_label(recorder.is_none(), &recordings);
+    }
+}
+
+fn watch_button(
+    row: &mut ChildBuilder,
+    label: &str,
+    button: WatchButton,
+    asset_server: &AssetServer,
+) {
+    row.spawn((
+        button,
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+                ..default()
+            },
+            background_color: tailwind::LIME_800.into(),
+            ..default()
+        },
+    ))
+    .with_children(|button| {
+        button.spawn(TextBundle::from_section(
+            label,
+            TextStyle {
+                font: asset_server.load("Kalam-Light.ttf"),
+                font_size: 20.0,
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Lists the recorded cycles that can be watched under the levels,
+/// and whether the cycles are recorded
+pub fn spawn_watch_buttons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    recorder: Option<Res<CycleRecorder>>,
+    recordings: Res<CycleRecordings>,
+) {
+    let mut levels = recordings
+        .last
+        .keys()
+        .chain(recordings.winning.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    levels.sort();
+    levels.dedup();
+
+    commands
+        .spawn((
+            StateScoped(GameState::LevelSelection),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(20.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                RecorderText,
+                TextBundle::from_section(
+                    recorder_label(recorder.is_some(), &recordings),
+                    TextStyle {
+                        font: asset_server.load("Kalam-Light.ttf"),
+                        font_size: 18.0,
+                        ..default()
+                    },
+                ),
+            ));
+            for level in levels {
+                panel
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(10.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(
+                            format!("Level {level}"),
+                            TextStyle {
+                                font: asset_server.load("Kalam-Light.ttf"),
+                                font_size: 20.0,
+                                ..default()
+                            },
+                        ));
+                        if recordings.winning.contains_key(&level) {
+                            let button = WatchButton {
+                                level,
+                                kind: PlaybackKind::WinningRun,
+                            };
+                            watch_button(row, "Watch winning run", button, &asset_server);
+                        }
+                        if recordings.last.contains_key(&level) {
+                            let button = WatchButton {
+                                level,
+                                kind: PlaybackKind::KillCam,
+                            };
+                            watch_button(row, "Kill cam", button, &asset_server);
+                        }
+                    });
+            }
+        });
+}
+
+pub fn interaction_on_watch_buttons(
+    mut commands: Commands,
+    mut query: Query<(&Interaction, &mut BackgroundColor, &WatchButton), Changed<Interaction>>,
+    recordings: Res<CycleRecordings>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut background_color, button) in query.iter_mut() {
+        *background_color = match *interaction {
+            Interaction::Pressed => {
+                let recording = match button.kind {
+                    PlaybackKind::WinningRun => recordings.winning.get(&button.level),
+                    PlaybackKind::KillCam => recordings.last.get(&button.level),
+                };
+                if let Some(recording) = recording {
+                    let (tick, speed) = match button.kind {
+                        PlaybackKind::WinningRun => (0, 1.0),
+                        PlaybackKind::KillCam => (recording.kill_cam_start(), 0.5),
+                    };
+                    commands.insert_resource(Playback {
+                        recording: Arc::clone(recording),
+                        tick: tick as f64,
+                        speed,
+                        paused: false,
+                    });
+                    game_state.set(GameState::Watch);
+                }
+                tailwind::LIME_300.into()
+            }
+            Interaction::Hovered => tailwind::LIME_500.into(),
+            Interaction::None => tailwind::LIME_800.into(),
+        }
+    }
+}
+
+pub fn spawn_playback_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        PlaybackText,
+        StateScoped(GameState::Watch),
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("Kalam-Light.ttf"),
+                font_size: 24.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        }),
+    ));
+}
+
+pub fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<Playback>();
+}
+
+/// Space pauses, Left and Right step, Up and Down change the speed
+/// and Escape goes back to the level selection
+pub fn control_playback(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_state.set(GameState::LevelSelection);
+    }
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    let step = if keyboard_input.pressed(KeyCode::ShiftLeft) {
+        SCRUB_STEP * 10
+    } else {
+        SCRUB_STEP
+    } as f64;
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        playback.tick = (playback.tick - step).max(0.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        playback.tick += step;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        playback.speed = (playback.speed * 2.0).min(8.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        playback.speed = (playback.speed / 2.0).max(0.125);
+    }
+}
+
+/// Moves the watched moment forward, it stops at the end of the cycle
+pub fn advance_playback(time: Res<Time<Real>>, mut playback: ResMut<Playback>) {
+    if !playback.paused {
+        playback.tick += time.delta_seconds_f64() * TICK_RATE * playback.speed;
+    }
+    let last = playback.recording.len().saturating_sub(1) as f64;
+    if playback.tick >= last {
+        playback.tick = last;
+        playback.paused = true;
+    }
+}
+
+/// Draws the level at the watched moment, attacks and deaths flash for a moment
+pub fn draw_playback(playback: Res<Playback>, mut gizmos: Gizmos) {
+    let recording = &playback.recording;
+    for wall in recording.walls.iter() {
+        gizmos.linestrip_2d(
+            wall.outline.iter().chain(wall.outline.first()).copied(),
+            Color::linear_rgb(0.3, 0.3, 0.3),
+        );
+    }
+
+    let tick = playback.tick as u64;
+    if let Some(bodies) = recording.frame(tick) {
+        draw_bodies(&mut gizmos, &bodies);
+    }
+
+    let flash = (TICK_RATE / 2.0) as u64;
+    let start = recording.events.partition_point(|(t, _)| *t + flash < tick);
+    for (t, event) in recording.events[start..].iter() {
+        if *t > tick {
+            break;
+        }
+        let Some(position) = event.position() else {
+            continue;
+        };
+        let fade = 1.0 - (tick - t) as f32 / flash as f32;
+        match event {
+            CycleEvent::Attack { velocity, .. } => {
+                gizmos.line_2d(
+                    position,
+                    position - velocity.normalize_or_zero() * 30.0,
+                    Color::srgba(1.0, 1.0, 0.6, fade),
+                );
+            }
+            _ => {
+                let color = Color::srgba(1.0, 0.1, 0.1, fade);
+                gizmos.line_2d(
+                    position - Vec2::splat(20.0),
+                    position + Vec2::splat(20.0),
+                    color,
+                );
+                gizmos.line_2d(
+                    position + Vec2::new(-20.0, 20.0),
+                    position + Vec2::new(20.0, -20.0),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+pub fn update_playback_text(
+    playback: Res<Playback>,
+    mut text: Query<&mut Text, With<PlaybackText>>,
+) {
+    let tick = playback.tick as u64;
+    let recording = &playback.recording;
+    // The last death, attacks are too frequent to be listed
+    let last = recording
+        .events
+        .iter()
+        .rev()
+        .filter(|(t, _)| *t <= tick)
+        .find_map(|(t, e)| {
+            let what = match e {
+                CycleEvent::EnnemyKilled { kind } => format!("{kind:?} killed"),
+                CycleEvent::GhostKilled { ghost, killer, .. } => {
+                    format!("ghost {} {killer}", ghost.0 + 1)
+                }
+                CycleEvent::PlayerKilled { .. } => "you were killed".to_string(),
+                CycleEvent::Attack { .. } => return None,
+            };
+            Some(format!("    {:.2}s: {what}", LevelTick(*t).as_secs()))
+        })
+        .unwrap_or_default();
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!(
+            "Level {}  {:.2}s / {:.2}s  x{}{}{last}\nSpace: pause    Left/Right: step    Up/Down: speed    Escape: back",
+            recording.level,
+            LevelTick(tick).as_secs(),
+            LevelTick(recording.len()).as_secs(),
+            playback.speed,
+            if playback.paused { "  paused" } else { "" },
+        );
+    }
+}
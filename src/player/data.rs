@@ -1,8 +1,15 @@
 use avian2d::prelude::*;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub const PLAYER_RADIUS: f32 = 15.0;
 
+/// Speed of the players, in pixels per second
+pub const PLAYER_SPEED: f32 = 160.0;
+
+/// Distance a player travels between two frames of its walk animation
+pub const ANIMATION_STRIDE: f32 = 13.0;
+
 /// Marker component of the player.
 ///
 /// This component allow to identify the playable character during Bevy queries.
@@ -14,7 +21,7 @@ pub struct Player;
 /// - The knight have lot of health and does damage with melee attacks.
 /// - The ranger shoots from long range but with low damage.
 /// - The wizard inflicts hight damages at medium range but is very weak.
-#[derive(Component, Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Class {
     Knight,
     Ranger,
@@ -33,6 +40,21 @@ pub enum PlayerType {
     Ghost,
 }
 
+/// What a player intends to do, held until the next action replaces it.
+///
+/// The live player gets it from the devices and the ghosts from their recording,
+/// both are then moved the same way so tuning the players keeps the recordings valid.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerAction {
+    /// Direction of the movement, its length is at most one
+    pub movement: Vec2,
+    /// Direction the player faces, zero keeps the current rotation
+    pub aim: Vec2,
+    pub attack: bool,
+    /// No class has an ability yet, it is recorded for when one does
+    pub ability: bool,
+}
+
 // Marker component indicating that the entity can be targeted
 #[derive(Component, Debug, Clone, Copy)]
 pub struct Targetable;
@@ -118,13 +140,16 @@ impl Attack {
 pub struct AttackProjectile {
     pub initial_position: Vec2,
     pub range: f32,
+    /// The entity that shot the projectile
+    pub shooter: Entity,
 }
 
 impl AttackProjectile {
-    pub fn new(initial_position: Vec2, range: f32) -> Self {
+    pub fn new(initial_position: Vec2, range: f32, shooter: Entity) -> Self {
         AttackProjectile {
             initial_position,
             range,
+            shooter,
         }
     }
 }
@@ -148,12 +173,14 @@ pub struct PlayerBundle {
     pub player_type: PlayerType,
     pub team: Team,
     pub targetable: Targetable,
+    pub action: PlayerAction,
 }
 
 impl PlayerBundle {
     pub fn new(
         player_type: PlayerType,
         class: Class,
+        position: Vec2,
         asset_server: &Res<AssetServer>,
         texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
     ) -> Self {
@@ -169,7 +196,7 @@ impl PlayerBundle {
             player_stats: PlayerStats::new(class),
             sprite_bundle: SpriteBundle {
                 texture,
-                transform: Transform::from_xyz(-400.0, 0.0, 0.0)
+                transform: Transform::from_translation(position.extend(0.0))
                     .with_scale(Vec3::new(0.3, 0.3, 0.3)),
                 ..default()
             },
@@ -187,6 +214,7 @@ impl PlayerBundle {
             player_type,
             team: Team::Player,
             targetable: Targetable,
+            action: PlayerAction::default(),
         }
     }
 }
@@ -1,42 +1,81 @@
+use std::fmt;
+
+use super::data::*;
+use crate::ennemy::prelude::*;
 use crate::level_history::prelude::*;
 use bevy::prelude::*;
 
+/// Sent when the action of a player changes, it is what the history records
 #[derive(Event, Debug, Clone)]
-pub struct PlayerMoveEvent {
+pub struct PlayerActionEvent {
     pub entity: Entity,
     pub source: EventSource,
-    pub delta: Vec2,
+    pub action: PlayerAction,
 }
 
+/// Position and rotation of the player at a keyframe tick
 #[derive(Event, Debug, Clone)]
-pub struct PlayerRotateEvent {
+pub struct PlayerKeyframeEvent {
     pub entity: Entity,
     pub source: EventSource,
-    pub to: Quat,
+    pub position: Vec2,
+    pub rotation: Quat,
 }
 
+/// Sent when the live player is hit, replayed to remove its ghost at the same tick
 #[derive(Event, Debug, Clone)]
-pub struct PlayerAttackEvent {
+pub struct PlayerKilledEvent {
     pub entity: Entity,
     pub source: EventSource,
 }
-
+/// Sent when a ghost is killed, recorded with the live player that saw it
+///
+/// A replayed death changes nothing, the ghosts die again from the simulation
 #[derive(Event, Debug, Clone)]
-pub struct PlayerKilledEvent {
-    pub entity: Entity,
+pub struct GhostKilledEvent {
     pub source: EventSource,
+    /// The ghost is already despawned when the event is read
+    pub ghost: GhostIdentifier,
+    pub killer: Killer,
+    pub position: Vec2,
+}
+
+/// What killed a ghost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Killer {
+    Ennemy(EnnemyKind),
+    /// The live player shot the ghost
+    Player,
+    /// Another ghost shot the ghost
+    Ghost(GhostIdentifier),
+    /// The shooter was gone when the projectile hit
+    Unknown,
+    /// The ghost was removed by the death its recording ends with
+    Recording,
+}
+
+impl fmt::Display for Killer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Killer::Ennemy(kind) => write!(f, "killed by {kind:?}"),
+            Killer::Player => write!(f, "killed by the player"),
+            Killer::Ghost(ghost) => write!(f, "killed by ghost {}", ghost.0 + 1),
+            Killer::Unknown => write!(f, "killed"),
+            Killer::Recording => write!(f, "died as recorded"),
+        }
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-//// PlayerMove Event
+// PlayerAction Event
 
-impl SetEntity for PlayerMoveEvent {
+impl SetEntity for PlayerActionEvent {
     fn set_entity(&mut self, entity: Entity) {
         self.entity = entity;
     }
 }
 
-impl EventSourceMethods for PlayerMoveEvent {
+impl EventSourceMethods for PlayerActionEvent {
     fn set_source(&mut self, source: EventSource) {
         self.source = source;
     }
@@ -45,22 +84,87 @@ impl EventSourceMethods for PlayerMoveEvent {
     }
 }
 
-impl EventRecordDebug for PlayerMoveEvent {
+impl EventRecordDebug for PlayerActionEvent {
     fn get_debug_color(&self, _: GhostIdentifier) -> Color {
-        Color::srgba(0.0, 0.0, 1.0, 0.2)
+        if self.action.attack {
+            Color::srgba(1.0, 0.0, 1.0, 0.5)
+        } else {
+            Color::srgba(0.0, 0.0, 1.0, 0.2)
+        }
+    }
+    fn get_debug_size(&self) -> Vec2 {
+        if self.action.attack {
+            Vec2::new(3.0, 16.0)
+        } else {
+            Vec2::new(1.0, 8.0)
+        }
+    }
+}
+
+impl ReplayCodec for PlayerActionEvent {
+    const TAG: [u8; 4] = *b"ACTN";
+    const HELD: bool = true;
+    fn encode(&self, writer: &mut ReplayWriter) {
+        writer.f32(self.action.movement.x);
+        writer.f32(self.action.movement.y);
+        writer.f32(self.action.aim.x);
+        writer.f32(self.action.aim.y);
+        writer.u8(self.action.attack as u8 | (self.action.ability as u8) << 1);
+    }
+    fn decode(reader: &mut ReplayReader) -> Result<Self, ReplayError> {
+        let movement = Vec2::new(reader.f32()?, reader.f32()?);
+        let aim = Vec2::new(reader.f32()?, reader.f32()?);
+        let buttons = reader.u8()?;
+        Ok(Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            action: PlayerAction {
+                movement,
+                aim,
+                attack: buttons & 1 != 0,
+                ability: buttons & 2 != 0,
+            },
+        })
+    }
+}
+impl RecordFields for PlayerActionEvent {
+    const KIND: &'static str = "action";
+    const FIELDS: &'static [&'static str] =
+        &["move_x", "move_y", "aim_x", "aim_y", "attack", "ability"];
+    fn to_fields(&self) -> Vec<f32> {
+        vec![
+            self.action.movement.x,
+            self.action.movement.y,
+            self.action.aim.x,
+            self.action.aim.y,
+            self.action.attack as u8 as f32,
+            self.action.ability as u8 as f32,
+        ]
+    }
+    fn from_fields(fields: &[f32]) -> Self {
+        Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            action: PlayerAction {
+                movement: Vec2::new(fields[0], fields[1]),
+                aim: Vec2::new(fields[2], fields[3]),
+                attack: fields[4] != 0.0,
+                ability: fields[5] != 0.0,
+            },
+        }
     }
 }
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-//// PlayerRotate Event
+// PlayerKeyframe Event
 
-impl SetEntity for PlayerRotateEvent {
+impl SetEntity for PlayerKeyframeEvent {
     fn set_entity(&mut self, entity: Entity) {
         self.entity = entity;
     }
 }
 
-impl EventSourceMethods for PlayerRotateEvent {
+impl EventSourceMethods for PlayerKeyframeEvent {
     fn set_source(&mut self, source: EventSource) {
         self.source = source;
     }
@@ -69,22 +173,72 @@ impl EventSourceMethods for PlayerRotateEvent {
     }
 }
 
-impl EventRecordDebug for PlayerRotateEvent {
+impl KeyframeEvent for PlayerKeyframeEvent {
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+    fn position(&self) -> Vec2 {
+        self.position
+    }
+}
+
+impl EventRecordDebug for PlayerKeyframeEvent {
     fn get_debug_color(&self, _: GhostIdentifier) -> Color {
-        Color::srgba(0.0, 1.0, 0.0, 0.2)
+        Color::srgba(1.0, 1.0, 0.0, 0.5)
+    }
+    fn get_debug_size(&self) -> Vec2 {
+        Vec2::new(2.0, 2.0)
+    }
+}
+
+impl ReplayCodec for PlayerKeyframeEvent {
+    const TAG: [u8; 4] = *b"KEYF";
+    fn encode(&self, writer: &mut ReplayWriter) {
+        writer.f32(self.position.x);
+        writer.f32(self.position.y);
+        for value in self.rotation.to_array() {
+            writer.f32(value);
+        }
+    }
+    fn decode(reader: &mut ReplayReader) -> Result<Self, ReplayError> {
+        Ok(Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            position: Vec2::new(reader.f32()?, reader.f32()?),
+            rotation: Quat::from_xyzw(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?),
+        })
+    }
+}
+impl RecordFields for PlayerKeyframeEvent {
+    const KIND: &'static str = "keyframe";
+    /// The rotation is the angle the player faces, in radians
+    const FIELDS: &'static [&'static str] = &["x", "y", "angle"];
+    fn to_fields(&self) -> Vec<f32> {
+        vec![
+            self.position.x,
+            self.position.y,
+            self.rotation.to_euler(EulerRot::XYZ).2,
+        ]
+    }
+    fn from_fields(fields: &[f32]) -> Self {
+        Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            position: Vec2::new(fields[0], fields[1]),
+            rotation: Quat::from_rotation_z(fields[2]),
+        }
     }
 }
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-//// PlayerAttack Event
+// PlayerKilled Event
 
-impl SetEntity for PlayerAttackEvent {
+impl SetEntity for PlayerKilledEvent {
     fn set_entity(&mut self, entity: Entity) {
         self.entity = entity;
     }
 }
-
-impl EventSourceMethods for PlayerAttackEvent {
+impl EventSourceMethods for PlayerKilledEvent {
     fn set_source(&mut self, source: EventSource) {
         self.source = source;
     }
@@ -93,21 +247,49 @@ impl EventSourceMethods for PlayerAttackEvent {
     }
 }
 
-impl EventRecordDebug for PlayerAttackEvent {
+impl EventRecordDebug for PlayerKilledEvent {
     fn get_debug_color(&self, _: GhostIdentifier) -> Color {
-        Color::srgba(1.0, 0.0, 1.0, 0.5)
+        Color::srgba(1.0, 0.0, 0.0, 0.9)
+    }
+    fn get_debug_size(&self) -> Vec2 {
+        Vec2::new(4.0, 20.0)
     }
 }
-////////////////////////////////////////////////////////////////////////////////////////////////////
 
-//// PlayerKilled Event
+impl ReplayCodec for PlayerKilledEvent {
+    const TAG: [u8; 4] = *b"KILD";
+    fn encode(&self, _: &mut ReplayWriter) {}
+    fn decode(_: &mut ReplayReader) -> Result<Self, ReplayError> {
+        Ok(Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+        })
+    }
+}
 
-impl SetEntity for PlayerKilledEvent {
-    fn set_entity(&mut self, entity: Entity) {
-        self.entity = entity;
+impl RecordFields for PlayerKilledEvent {
+    const KIND: &'static str = "killed";
+    const FIELDS: &'static [&'static str] = &[];
+    fn to_fields(&self) -> Vec<f32> {
+        vec![]
+    }
+    fn from_fields(_: &[f32]) -> Self {
+        Self {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+        }
     }
 }
-impl EventSourceMethods for PlayerKilledEvent {
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+// GhostKilled Event
+
+/// The event names its ghost, it is not sent to an entity
+impl SetEntity for GhostKilledEvent {
+    fn set_entity(&mut self, _: Entity) {}
+}
+
+impl EventSourceMethods for GhostKilledEvent {
     fn set_source(&mut self, source: EventSource) {
         self.source = source;
     }
@@ -116,8 +298,100 @@ impl EventSourceMethods for PlayerKilledEvent {
     }
 }
 
-impl EventRecordDebug for PlayerKilledEvent {
+impl EventRecordDebug for GhostKilledEvent {
     fn get_debug_color(&self, _: GhostIdentifier) -> Color {
-        Color::srgba(1.0, 0.0, 1.0, 0.5)
+        Color::srgba(1.0, 0.5, 0.0, 0.9)
+    }
+    fn get_debug_size(&self) -> Vec2 {
+        Vec2::new(4.0, 14.0)
+    }
+}
+
+impl Killer {
+    /// Number of the killer and of its ennemy kind or ghost, as saved
+    fn code(&self) -> (u8, u64) {
+        match self {
+            Killer::Unknown => (0, 0),
+            Killer::Recording => (1, 0),
+            Killer::Player => (2, 0),
+            Killer::Ghost(ghost) => (3, ghost.0 as u64),
+            Killer::Ennemy(EnnemyKind::Dummy) => (4, 0),
+            Killer::Ennemy(EnnemyKind::Turret) => (4, 1),
+        }
+    }
+
+    fn from_code(code: u8, id: u64) -> Option<Self> {
+        match (code, id) {
+            (0, _) => Some(Killer::Unknown),
+            (1, _) => Some(Killer::Recording),
+            (2, _) => Some(Killer::Player),
+            (3, ghost) => Some(Killer::Ghost(GhostIdentifier(ghost as usize))),
+            (4, 0) => Some(Killer::Ennemy(EnnemyKind::Dummy)),
+            (4, 1) => Some(Killer::Ennemy(EnnemyKind::Turret)),
+            _ => None,
+        }
+    }
+}
+
+impl ReplayCodec for GhostKilledEvent {
+    const TAG: [u8; 4] = *b"GDTH";
+    const NAMES_GHOSTS: bool = true;
+    fn encode(&self, writer: &mut ReplayWriter) {
+        let (killer, id) = self.killer.code();
+        writer.varint(self.ghost.0 as u64);
+        writer.u8(killer);
+        writer.varint(id);
+        writer.f32(self.position.x);
+        writer.f32(self.position.y);
+    }
+    fn decode(reader: &mut ReplayReader) -> Result<Self, ReplayError> {
+        let ghost = GhostIdentifier(reader.varint()? as usize);
+        let code = reader.u8()?;
+        let killer =
+            Killer::from_code(code, reader.varint()?).ok_or(ReplayError::UnknownKiller(code))?;
+        Ok(Self {
+            source: EventSource::Replay,
+            ghost,
+            killer,
+            position: Vec2::new(reader.f32()?, reader.f32()?),
+        })
+    }
+    /// The death is dropped with its ghost, a deleted shooter becomes unknown
+    fn remap_ghosts(&mut self, map: &dyn Fn(GhostIdentifier) -> Option<GhostIdentifier>) -> bool {
+        if let Killer::Ghost(ghost) = self.killer {
+            self.killer = map(ghost).map_or(Killer::Unknown, Killer::Ghost);
+        }
+        match map(self.ghost) {
+            Some(ghost) => {
+                self.ghost = ghost;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl RecordFields for GhostKilledEvent {
+    const KIND: &'static str = "ghost_killed";
+    /// The killer is 0 when unknown, 1 for the end of the recording, 2 for the player,
+    /// 3 for a ghost and 4 for an ennemy, `killer_id` is the ghost or the ennemy kind
+    const FIELDS: &'static [&'static str] = &["dead_ghost", "killer", "killer_id", "x", "y"];
+    fn to_fields(&self) -> Vec<f32> {
+        let (killer, id) = self.killer.code();
+        vec![
+            self.ghost.0 as f32,
+            killer as f32,
+            id as f32,
+            self.position.x,
+            self.position.y,
+        ]
+    }
+    fn from_fields(fields: &[f32]) -> Self {
+        Self {
+            source: EventSource::Replay,
+            ghost: GhostIdentifier(fields[0] as usize),
+            killer: Killer::from_code(fields[1] as u8, fields[2] as u64).unwrap_or(Killer::Unknown),
+            position: Vec2::new(fields[3], fields[4]),
+        }
     }
 }
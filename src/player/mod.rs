@@ -7,24 +7,31 @@ use bevy::prelude::*;
 use events::*;
 use systems::*;
 
-use crate::game::GameState;
-use crate::level_history::LevelHistorySet;
+use crate::game::{GameState, LevelSetup, PlayState, UnscoredRun};
+use crate::level_history::prelude::*;
+use crate::level_history::{detect_divergence, LevelHistorySet};
+use crate::timeline::prelude::*;
 
 pub mod prelude {
     pub use super::data::*;
     pub use super::events::*;
-    pub use super::PlayerPlugin;
+    pub use super::{add_player_recorded_events, PlayerControlPlugin, PlayerPlugin};
+}
+
+/// Registers the events recorded for the players, in an app or in a bare [`RecordedEvents`]
+pub fn add_player_recorded_events<R: RecordedEventAppExt>(registry: &mut R) -> &mut R {
+    registry
+        .add_recorded_event::<PlayerActionEvent>()
+        .add_recorded_event::<PlayerKeyframeEvent>()
+        .add_recorded_event::<PlayerKilledEvent>()
+        .add_recorded_event::<GhostKilledEvent>()
 }
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<PlayerMoveEvent>()
-            .add_event::<PlayerRotateEvent>()
-            .add_event::<PlayerAttackEvent>()
-            .add_event::<PlayerKilledEvent>()
-            .add_systems(OnEnter(GameState::Play), spawn_player)
+        add_player_recorded_events(app)
             .add_systems(
                 OnEnter(GameState::GameOver),
                 despawn_player.after(LevelHistorySet::SavePlayer),
@@ -33,19 +40,52 @@ impl Plugin for PlayerPlugin {
             .add_systems(OnEnter(GameState::CharacterSelection), despawn_player)
             .add_systems(
                 Update,
+                check_for_level_complete
+                    .run_if(in_state(PlayState::Running))
+                    .run_if(not(resource_exists::<UnscoredRun>)),
+            )
+            .add_systems(
+                FixedUpdate,
                 (
-                    move_player_write,
-                    move_player_read,
-                    rotate_player_write,
-                    rotate_player_read,
-                    player_attack_write,
-                    player_attack_read,
-                    player_killed_read,
-                    check_for_level_complete,
+                    player_keyframe_write.run_if(not(resource_exists::<FastForward>)),
+                    detect_divergence::<PlayerKeyframeEvent>,
+                    player_keyframe_read,
+                    correct_drift,
+                    player_action_read,
+                    // Shots leave in the direction aimed at on the same tick
+                    (
+                        move_player,
+                        rotate_player,
+                        player_attack,
+                        player_killed_read,
+                    )
+                        .chain(),
                     despawn_out_of_range_projectiles,
                 )
+                    .chain()
+                    .after(LevelHistorySet::Replay)
+                    .before(LevelHistorySet::Record)
                     .run_if(in_state(GameState::Play)),
             )
             .add_systems(PostProcessCollisions, handle_projectile_colissions);
     }
 }
+
+/// Spawns the live player and drives it with the keyboard and the mouse
+///
+/// Left out of headless apps, where only ghosts are simulated
+pub struct PlayerControlPlugin;
+
+impl Plugin for PlayerControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(LevelSetup, spawn_player).add_systems(
+            FixedUpdate,
+            player_action_write
+                .after(LevelHistorySet::Replay)
+                .before(player_action_read)
+                .run_if(in_state(GameState::Play))
+                .run_if(not(resource_exists::<FastForward>))
+                .run_if(not(resource_exists::<Scrubbing>)),
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use crate::game::{CurrentLevel, GameState, Levels};
+use crate::character::prelude::SelectedCharacter;
+use crate::ennemy::prelude::*;
+use crate::game::{CurrentLevel, GameState, LevelBody, LevelCompletedEvent, Levels};
 use crate::level_history::prelude::*;
-use crate::{character::prelude::SelectedCharacter, Ennemy};
+use crate::levels::prelude::*;
 
 use super::prelude::*;
 use avian2d::prelude::*;
@@ -8,7 +10,7 @@ use bevy::{prelude::*, window::PrimaryWindow};
 
 /// Spawn the player to the map.
 ///
-/// For now the player is spawned to the leftof the level.
+/// The player is spawned where the current level starts the cycles.
 pub fn spawn_player(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -16,14 +18,22 @@ pub fn spawn_player(
     selected_character: Res<SelectedCharacter>,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    current_level: Res<CurrentLevel>,
+    definitions: Res<Assets<LevelDefinition>>,
 ) {
+    let spawn = current_level
+        .definition(&definitions)
+        .map(|level| level.spawn)
+        .unwrap_or_default();
     commands
         .spawn((
             StateScoped(GameState::Play),
+            LevelBody,
             Player,
             PlayerBundle::new(
                 PlayerType::Alive,
-                selected_character.0.clone(),
+                selected_character.0,
+                spawn,
                 &asset_server,
                 &mut texture_atlas_layouts,
             ),
@@ -47,177 +57,218 @@ pub fn despawn_player(mut commands: Commands, query_player: Query<Entity, With<P
     }
 }
 
-/// Moves the player around.
+/// Turns the devices in to the action of the live player.
 ///
-/// Use ZQSD (or WASD) to move the player around.
-pub fn move_player_write(
-    time: Res<Time>,
+/// Use ZQSD (or WASD) to move, the mouse to aim and the left click to attack.
+/// The action is only sent when it changes.
+pub fn player_action_write(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player: Query<Entity, With<Player>>,
-    mut events: EventWriter<PlayerMoveEvent>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    player: Query<(Entity, &Transform, &PlayerAction), With<Player>>,
+    query_window: Query<&Window, With<PrimaryWindow>>,
+    query_camera: Query<(&Camera, &GlobalTransform)>,
+    mut events: EventWriter<PlayerActionEvent>,
 ) {
-    let delta_time = time.delta_seconds();
-
-    let mut direction = Vec2::ZERO;
+    let Ok((entity, transform, current)) = player.get_single() else {
+        return;
+    };
 
-    for entity in &mut player {
-        if keyboard_input.pressed(KeyCode::KeyW) {
-            direction.y += 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyS) {
-            direction.y -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyA) {
-            direction.x -= 1.0;
-        }
-        if keyboard_input.pressed(KeyCode::KeyD) {
-            direction.x += 1.0;
-        }
+    let mut movement = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::KeyW) {
+        movement.y += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyS) {
+        movement.y -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyA) {
+        movement.x -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::KeyD) {
+        movement.x += 1.0;
+    }
 
-        direction = direction.normalize_or_zero() * delta_time * 10000.0;
+    // Keeps the current aim while the cursor is outside the window
+    let (camera, camera_transform) = query_camera.single();
+    let aim = query_window
+        .single()
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| (ray.origin.truncate() - transform.translation.truncate()).normalize_or_zero())
+        .unwrap_or(current.aim);
 
-        events.send(PlayerMoveEvent {
-            delta: direction,
-            source: EventSource::Input,
+    let action = PlayerAction {
+        movement: movement.normalize_or_zero(),
+        aim,
+        attack: mouse.pressed(MouseButton::Left),
+        ability: mouse.pressed(MouseButton::Right),
+    };
+    if action != *current {
+        events.send(PlayerActionEvent {
             entity,
+            source: EventSource::Input,
+            action,
         });
     }
 }
 
-pub fn move_player_read(
-    mut player: Query<(&mut LinearVelocity, &mut Animation, &mut TextureAtlas)>,
-    mut events: EventReader<PlayerMoveEvent>,
+/// Hands the actions over to the players, whether they come from the devices or a recording
+pub fn player_action_read(
+    mut player: Query<&mut PlayerAction>,
+    mut events: EventReader<PlayerActionEvent>,
 ) {
     for event in events.read() {
-        if let Ok((mut velocity, mut animation, mut atlas)) = player.get_mut(event.entity) {
-            let travelled = Vec2::new(event.delta.x, event.delta.y).length();
-            animation.travelled += travelled;
-
-            if animation.travelled >= 800.0 {
-                atlas.index = if atlas.index == animation.indices.1 {
-                    animation.indices.0
-                } else {
-                    atlas.index + 1
-                };
-                animation.travelled -= 800.0
-            }
-
-            velocity.x = event.delta.x;
-            velocity.y = event.delta.y;
+        if let Ok(mut action) = player.get_mut(event.entity) {
+            *action = event.action;
         }
     }
 }
 
-/// Rotate the player around himself/herself.
-///
-/// Move the mouse around the player to make him rotate.
-pub fn rotate_player_write(
-    player: Query<(Entity, &Transform), With<Player>>,
-    query_window: Query<&Window, With<PrimaryWindow>>,
-    query_camera: Query<(&Camera, &GlobalTransform)>,
-    mut events: EventWriter<PlayerRotateEvent>,
+pub fn move_player(
+    time: Res<Time>,
+    mut player: Query<(
+        &PlayerAction,
+        &mut LinearVelocity,
+        &mut Animation,
+        &mut TextureAtlas,
+    )>,
 ) {
-    let (camera, camera_transform) = query_camera.single();
-    let window = query_window.single();
+    for (action, mut velocity, mut animation, mut atlas) in player.iter_mut() {
+        let movement = action.movement.clamp_length_max(1.0) * PLAYER_SPEED;
 
-    // Checks that cursor is inside the window
-    // The converts its coordinates to the world
-    if let Some(cursor_position) = window
-        .cursor_position()
-        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
-        .map(|ray| ray.origin.truncate())
-    {
-        if let Ok((entity, transform)) = player.get_single() {
-            let player_position = transform.translation.truncate();
-            let direction = cursor_position - player_position;
-            let angle = direction.y.atan2(direction.x);
-
-            events.send(PlayerRotateEvent {
-                to: Quat::from_rotation_z(angle),
-                source: EventSource::Input,
-                entity,
-            });
+        animation.travelled += movement.length() * time.delta_seconds();
+        if animation.travelled >= ANIMATION_STRIDE {
+            atlas.index = if atlas.index == animation.indices.1 {
+                animation.indices.0
+            } else {
+                atlas.index + 1
+            };
+            animation.travelled -= ANIMATION_STRIDE;
         }
+
+        velocity.0 = movement;
     }
 }
 
-pub fn rotate_player_read(
-    mut player: Query<&mut Transform, With<Player>>,
-    mut events: EventReader<PlayerRotateEvent>,
-) {
-    for event in events.read() {
-        if let Ok(mut transform) = player.get_mut(event.entity) {
-            transform.rotation = event.to;
+/// Rotate the player around himself/herself, toward where it aims.
+pub fn rotate_player(mut player: Query<(&PlayerAction, &mut Transform)>) {
+    for (action, mut transform) in player.iter_mut() {
+        if action.aim != Vec2::ZERO {
+            transform.rotation = Quat::from_rotation_z(action.aim.y.atan2(action.aim.x));
         }
     }
 }
 
-/// Attacks with player weapon.
-///
-/// Use left mouse click to perform an attack with the player's weapon.
-pub fn player_attack_write(
-    mut player: Query<(Entity, &mut PlayerStats), With<Player>>,
-    mouse: Res<ButtonInput<MouseButton>>,
-    time: Res<Time>,
-    mut events: EventWriter<PlayerAttackEvent>,
+/// Records where the live player is every [`KEYFRAME_INTERVAL`] ticks.
+pub fn player_keyframe_write(
+    tick: Res<LevelTick>,
+    player: Query<(Entity, &Transform), With<Player>>,
+    mut events: EventWriter<PlayerKeyframeEvent>,
 ) {
-    let Ok((entity, mut stats)) = player.get_single_mut() else {
-        return;
-    };
-    stats.attack.attack_speed.tick(time.delta());
-
-    if !mouse.pressed(MouseButton::Left) {
+    if tick.0 % KEYFRAME_INTERVAL != 0 {
         return;
     }
-    if stats.attack.attack_speed.finished() {
-        events.send(PlayerAttackEvent {
+    for (entity, transform) in player.iter() {
+        events.send(PlayerKeyframeEvent {
             entity,
             source: EventSource::Input,
+            position: transform.translation.truncate(),
+            rotation: transform.rotation,
         });
-        stats.attack.attack_speed.reset();
     }
 }
 
-pub fn player_attack_read(
+/// Brings replayed ghosts back to their recorded keyframes.
+///
+/// The live player is never corrected, its keyframes are the reference.
+pub fn player_keyframe_read(
+    mut commands: Commands,
+    mut player: Query<&mut Transform>,
+    correction: Res<KeyframeCorrection>,
+    mut events: EventReader<PlayerKeyframeEvent>,
+) {
+    for event in events.read() {
+        if event.source != EventSource::Replay {
+            continue;
+        }
+        let Ok(mut transform) = player.get_mut(event.entity) else {
+            continue;
+        };
+        transform.rotation = event.rotation;
+        match *correction {
+            KeyframeCorrection::Snap => {
+                transform.translation = event.position.extend(transform.translation.z);
+            }
+            KeyframeCorrection::Smooth { ticks } => {
+                commands.entity(event.entity).insert(DriftCorrection {
+                    offset: event.position - transform.translation.truncate(),
+                    ticks_left: ticks.max(1),
+                });
+            }
+        }
+    }
+}
+
+/// Applies one tick worth of the pending drift correction of the ghosts.
+pub fn correct_drift(
     mut commands: Commands,
-    player: Query<(&Transform, &PlayerStats)>,
+    mut ghosts: Query<(Entity, &mut Transform, &mut DriftCorrection)>,
+) {
+    for (entity, mut transform, mut correction) in ghosts.iter_mut() {
+        let step = correction.offset / correction.ticks_left as f32;
+        transform.translation += step.extend(0.0);
+        correction.offset -= step;
+        correction.ticks_left -= 1;
+        if correction.ticks_left == 0 {
+            commands.entity(entity).remove::<DriftCorrection>();
+        }
+    }
+}
+
+/// Attacks with player weapon as long as the attack is held.
+pub fn player_attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player: Query<(Entity, &Transform, &PlayerAction, &mut PlayerStats)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut events: EventReader<PlayerAttackEvent>,
 ) {
-    for event in events.read() {
-        if let Ok((transform, stats)) = player.get(event.entity) {
-            let attack = &stats.attack;
+    for (entity, transform, action, mut stats) in player.iter_mut() {
+        stats.attack.attack_speed.tick(time.delta());
+        if !action.attack || !stats.attack.attack_speed.finished() {
+            continue;
+        }
+        stats.attack.attack_speed.reset();
+        let attack = &stats.attack;
 
-            // Projectile size
-            let width = attack.size.x;
-            let height = attack.size.y;
+        // Projectile size
+        let width = attack.size.x;
+        let height = attack.size.y;
 
-            // Projectile transform
-            let position =
-                transform.translation + transform.rotation * Vec3::X * (PLAYER_RADIUS + 10.0);
-            let rotation = transform.rotation;
+        // Projectile transform
+        let position =
+            transform.translation + transform.rotation * Vec3::X * (PLAYER_RADIUS + 10.0);
+        let rotation = transform.rotation;
 
-            // Projectile movement
-            let direction = position - transform.translation;
-            let speed = attack.speed;
-            let velocity = direction * speed;
+        // Projectile movement
+        let direction = position - transform.translation;
+        let speed = attack.speed;
+        let velocity = direction * speed;
 
-            commands.spawn((
-                StateScoped(GameState::Play),
-                AttackProjectile::new(transform.translation.truncate(), attack.range),
-                ColorMesh2dBundle {
-                    mesh: meshes.add(Rectangle::new(height, width)).into(),
-                    material: materials.add(Color::linear_rgb(0.8, 0.6, 0.8)),
-                    transform: Transform::from_translation(position).with_rotation(rotation),
-                    ..default()
-                },
-                Team::Player,
-                RigidBody::Dynamic,
-                LinearVelocity(velocity.truncate()),
-                Collider::rectangle(height, width),
-            ));
-        }
+        commands.spawn((
+            StateScoped(GameState::Play),
+            LevelBody,
+            AttackProjectile::new(transform.translation.truncate(), attack.range, entity),
+            ColorMesh2dBundle {
+                mesh: meshes.add(Rectangle::new(height, width)).into(),
+                material: materials.add(Color::linear_rgb(0.8, 0.6, 0.8)),
+                transform: Transform::from_translation(position).with_rotation(rotation),
+                ..default()
+            },
+            Team::Player,
+            RigidBody::Dynamic,
+            LinearVelocity(velocity.truncate()),
+            Collider::rectangle(height, width),
+        ));
     }
 }
 
@@ -240,20 +291,49 @@ pub fn despawn_out_of_range_projectiles(
 
 pub fn handle_projectile_colissions(
     mut commands: Commands,
-    projectiles: Query<(Entity, &CollidingEntities), With<AttackProjectile>>,
-    ennemy_query: Query<(&Team, Option<&PlayerType>)>,
+    projectiles: Query<(Entity, &CollidingEntities, &AttackProjectile)>,
+    ennemy_query: Query<(&Team, Option<&PlayerType>, Option<&EnnemyKind>)>,
+    ghosts: Query<(&GhostIdentifier, &Transform)>,
     mut player_killed_event: EventWriter<PlayerKilledEvent>,
+    mut ghost_killed_event: EventWriter<GhostKilledEvent>,
+    mut ennemy_killed_event: EventWriter<EnnemyKilledEvent>,
 ) {
-    for (entity, colliding_entities) in projectiles.iter() {
+    for (entity, colliding_entities, projectile) in projectiles.iter() {
         for colliding_entity in colliding_entities.iter() {
             match ennemy_query.get(*colliding_entity) {
-                Ok((Team::Player, Some(PlayerType::Alive))) => {
+                Ok((Team::Player, Some(PlayerType::Alive), _)) => {
                     player_killed_event.send(PlayerKilledEvent {
                         entity: *colliding_entity,
                         source: EventSource::Input,
                     });
                 }
-                Ok(_) => {
+                Ok((Team::Player, Some(PlayerType::Ghost), _)) => {
+                    if let Ok((ghost, transform)) = ghosts.get(*colliding_entity) {
+                        let killer = match ennemy_query.get(projectile.shooter) {
+                            Ok((_, Some(PlayerType::Alive), _)) => Killer::Player,
+                            Ok((_, Some(PlayerType::Ghost), _)) => ghosts
+                                .get(projectile.shooter)
+                                .map_or(Killer::Unknown, |(shooter, _)| Killer::Ghost(*shooter)),
+                            Ok((_, _, Some(kind))) => Killer::Ennemy(*kind),
+                            _ => Killer::Unknown,
+                        };
+                        ghost_killed_event.send(GhostKilledEvent {
+                            source: EventSource::Input,
+                            ghost: *ghost,
+                            killer,
+                            position: transform.translation.truncate(),
+                        });
+                    }
+                    commands.entity(*colliding_entity).despawn_recursive();
+                }
+                Ok((_, _, kind)) => {
+                    if let Some(kind) = kind {
+                        ennemy_killed_event.send(EnnemyKilledEvent {
+                            ennemy: *colliding_entity,
+                            kind: *kind,
+                            killer: projectile.shooter,
+                        });
+                    }
                     commands.entity(*colliding_entity).despawn_recursive();
                     debug!("killed enemy {colliding_entity:?}");
                 }
@@ -261,7 +341,7 @@ pub fn handle_projectile_colissions(
             }
         }
 
-        if colliding_entities.len() > 0 {
+        if !colliding_entities.is_empty() {
             // despawn the projectile
             commands.entity(entity).despawn();
             debug!("Despawning projectile on collsion {entity:?}");
@@ -273,24 +353,44 @@ pub fn player_killed_read(
     mut commands: Commands,
     mut player_killed_event: EventReader<PlayerKilledEvent>,
     mut save_player_ghost_event: EventWriter<SavePlayerGhostEvent>,
-    player_query: Query<(&PlayerType, &Class)>,
+    mut ghost_killed_event: EventWriter<GhostKilledEvent>,
+    player_query: Query<(&PlayerType, &Class, &Transform, Option<&GhostIdentifier>)>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
     for e in player_killed_event.read() {
-        if let Ok((PlayerType::Alive, class)) = player_query.get(e.entity) {
-            game_state.set(GameState::CharacterSelection);
-            save_player_ghost_event.send(SavePlayerGhostEvent { class: *class });
+        match player_query.get(e.entity) {
+            Ok((PlayerType::Alive, class, _, _)) => {
+                game_state.set(GameState::CharacterSelection);
+                save_player_ghost_event.send(SavePlayerGhostEvent { class: *class });
+            }
+            Ok((PlayerType::Ghost, _, transform, Some(ghost))) => {
+                ghost_killed_event.send(GhostKilledEvent {
+                    source: EventSource::Input,
+                    ghost: *ghost,
+                    killer: Killer::Recording,
+                    position: transform.translation.truncate(),
+                });
+            }
+            _ => {}
+        }
+        // The ghost may already have been hit by the replayed projectile
+        if let Some(entity) = commands.get_entity(e.entity) {
+            entity.despawn_recursive();
         }
-        commands.entity(e.entity).despawn_recursive();
     }
 }
 
+/// Completes the level once every ennemy is dead
+///
+/// The score is the number of ghosts taking part plus the live player, if there is one
 pub fn check_for_level_complete(
     query: Query<(), With<Ennemy>>,
+    player: Query<(), With<Player>>,
     mut levels: ResMut<Levels>,
     mut current_level: ResMut<CurrentLevel>,
     mut game_state: ResMut<NextState<GameState>>,
     player_ghost_list: Res<PlayerGhostList>,
+    mut level_completed_event: EventWriter<LevelCompletedEvent>,
 ) {
     if query.iter().len() == 0 {
         let Some(level) = &current_level.0 else {
@@ -300,11 +400,15 @@ pub fn check_for_level_complete(
 
         info!("Completed level {} !!!!", level.id);
 
-        if levels.0.len() == level.id + 1 {
-            levels.unlock_level(level.id + 1);
-        }
-
-        levels.set_next_score(level.id, player_ghost_list.ghosts.len() + 1);
+        let ghosts = (0..player_ghost_list.ghosts.len())
+            .filter(|i| player_ghost_list.is_spawned(GhostIdentifier(*i)))
+            .count();
+        let cycles = ghosts + player.iter().len();
+        levels.set_next_score(level.id, cycles);
+        level_completed_event.send(LevelCompletedEvent {
+            level: level.id,
+            cycles,
+        });
         current_level.0 = None;
         game_state.set(GameState::LevelSelection);
     }
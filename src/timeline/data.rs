@@ -0,0 +1,224 @@
+use crate::ennemy::prelude::*;
+use crate::level_history::prelude::*;
+use crate::player::prelude::*;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// Number of ticks between two snapshots of the level
+///
+/// The timeline can only be scrubbed to a snapshot
+pub const SNAPSHOT_INTERVAL: u64 = 12;
+
+/// Ticks skipped by one step of the timeline, holding shift skips ten steps
+pub const SCRUB_STEP: u64 = SNAPSHOT_INTERVAL;
+
+/// Shortcuts taking over the first ghosts while scrubbing, the first key takes over the first ghost
+///
+/// Any ghost is taken over by clicking its [`TimelineLane`]
+pub const FORK_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// What a body of a snapshot was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyKind {
+    Player,
+    Ghost,
+    Ennemy(EnnemyKind),
+    Projectile(Team),
+}
+
+impl BodyKind {
+    pub fn radius(&self) -> f32 {
+        match self {
+            BodyKind::Player | BodyKind::Ghost => PLAYER_RADIUS,
+            BodyKind::Ennemy(kind) => kind.radius(),
+            BodyKind::Projectile(_) => 7.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            BodyKind::Player => Color::srgb(0.2, 0.8, 0.2),
+            BodyKind::Ghost => Color::srgba(0.6, 0.6, 1.0, 0.8),
+            BodyKind::Ennemy(_) => Color::srgb(0.8, 0.3, 0.1),
+            BodyKind::Projectile(Team::Player) => Color::srgb(0.8, 0.6, 0.8),
+            BodyKind::Projectile(Team::Enemy) => Color::srgb(0.9, 0.4, 0.6),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodySnapshot {
+    pub kind: BodyKind,
+    pub position: Vec2,
+    pub rotation: Quat,
+}
+
+/// Where every body of the level was at the start of a tick
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub bodies: Vec<BodySnapshot>,
+}
+
+/// The snapshots of the current cycle, sorted by tick
+#[derive(Resource, Default)]
+pub struct TimelineSnapshots(pub Vec<Snapshot>);
+
+impl TimelineSnapshots {
+    /// The last snapshot taken at or before `tick`
+    pub fn at(&self, tick: u64) -> Option<&Snapshot> {
+        let index = self.0.partition_point(|s| s.tick <= tick);
+        index.checked_sub(1).map(|i| &self.0[i])
+    }
+}
+
+/// Present while the cycle is paused to scrub the timeline
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Scrubbing {
+    /// The moment shown
+    pub tick: u64,
+    /// The moment the cycle was paused at
+    pub end: u64,
+}
+
+/// Most ticks simulated in one frame while the level is simulated up to a sought moment
+pub const FAST_FORWARD_STEPS: u64 = 4 * TICK_RATE as u64;
+
+/// Time a frame spends simulating a sought moment, the game keeps drawing during long seeks
+pub const FAST_FORWARD_BUDGET: Duration = Duration::from_millis(20);
+
+/// Present while the level is simulated up to the moment it resumes from
+///
+/// The virtual clock waits paused while ticks are simulated each frame, up to
+/// [`FAST_FORWARD_STEPS`] or for [`FAST_FORWARD_BUDGET`]. Nothing is recorded
+/// for the live player during that time, its records are already in the history
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct FastForward {
+    /// The tick the cycle resumes from
+    pub target: u64,
+}
+
+/// Marker of the text showing how far the level is simulated
+#[derive(Component)]
+pub struct FastForwardText;
+
+/// Marker of the text describing the scrubbed moment
+#[derive(Component)]
+pub struct ScrubText;
+
+/// Marker of the text listing the ghosts that went off-script or died
+#[derive(Component)]
+pub struct IncidentText;
+
+/// Speeds the cycle can be played at, slower for precise timing or faster to wait less
+pub const TIME_SCALES: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// Index in [`TIME_SCALES`] of the speed of the cycles
+///
+/// It only scales the virtual clock, the simulation still steps on fixed ticks
+/// so recordings do not depend on the speed they were made at
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeScale(pub usize);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+impl TimeScale {
+    pub fn speed(&self) -> f64 {
+        TIME_SCALES[self.0]
+    }
+}
+
+/// Marker of the text showing the speed of the cycle
+#[derive(Component)]
+pub struct TimeScaleText;
+
+/// Marker of the text showing how much the histories hold
+#[derive(Component)]
+pub struct HistoryUsageText;
+
+/// Shows on the lane of a ghost when it went off-script or died
+#[derive(Component)]
+pub struct IncidentMarker;
+
+/// Height of the lane of one ghost in the timeline panel, in pixels
+pub const LANE_HEIGHT: f32 = 20.0;
+
+/// Width of the names of the lanes, in pixels
+pub const LANE_LABEL_WIDTH: f32 = 90.0;
+
+/// The part of the cycle shown by the timeline panel
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct TimelineView {
+    /// Tick at the left edge of the lanes
+    pub start: f32,
+    pub pixels_per_tick: f32,
+    /// Keeps the current tick in view, panning turns it off
+    pub follow: bool,
+}
+
+impl Default for TimelineView {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            pixels_per_tick: 0.1,
+            follow: true,
+        }
+    }
+}
+
+/// Root node of the timeline panel
+#[derive(Component)]
+pub struct TimelinePanel;
+
+/// The lane of a ghost, or of the live player, clicking it while scrubbing takes over the ghost
+#[derive(Component)]
+pub struct TimelineLane(pub GhostIdentifier);
+
+/// The visible part of a lane, it clips the markers outside the view
+#[derive(Component)]
+pub struct TimelineTrack;
+
+/// Holds the markers of a ghost, it is moved to pan the view
+#[derive(Component)]
+pub struct TimelineStrip(pub GhostIdentifier);
+
+/// Shows the current tick, or the scrubbed one, on every lane
+#[derive(Component)]
+pub struct TimelineCursor;
+
+/// A recorded event on the timeline
+#[derive(Component)]
+pub struct TimelineMarker {
+    pub tick: u64,
+    pub width: f32,
+}
+
+impl TimelineMarker {
+    pub fn left(&self, view: &TimelineView) -> Val {
+        Val::Px(self.tick as f32 * view.pixels_per_tick - self.width / 2.0)
+    }
+}
+
+/// The markers of one recorded event type
+#[derive(Default)]
+pub struct MarkerSync {
+    pub markers: Vec<Entity>,
+    /// The history the markers were built from
+    pub state: Option<HistoryState>,
+    /// Records from this tick onward have no marker yet
+    pub next_tick: u64,
+}
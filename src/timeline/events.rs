@@ -0,0 +1,28 @@
+use crate::level_history::prelude::*;
+use bevy::prelude::*;
+
+/// Resumes the cycle from an earlier tick
+///
+/// The live player's records from that tick onward are discarded,
+/// the ghosts keep all of theirs
+#[derive(Event, Debug, Clone)]
+pub struct SeekEvent {
+    pub tick: u64,
+}
+
+/// Plays the cycle again, with the same ghosts and without the run of the live player
+///
+/// A cycle that took over a ghost restarts from the takeover,
+/// the ghost's records before it are not part of the run
+#[derive(Event, Debug, Clone)]
+pub struct RestartCycleEvent;
+
+/// Takes over a ghost from a tick of the cycle
+///
+/// The ghost's records from that tick onward are replaced by the input of the
+/// live player, which plays its class. The run of the live player so far is discarded
+#[derive(Event, Debug, Clone)]
+pub struct ForkEvent {
+    pub ghost: GhostIdentifier,
+    pub tick: u64,
+}
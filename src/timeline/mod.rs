@@ -0,0 +1,93 @@
+mod data;
+mod events;
+mod systems;
+
+use bevy::prelude::*;
+use data::*;
+use events::*;
+use systems::*;
+
+use crate::game::{setup_level, GameState, PlayState};
+use crate::level_history::LevelHistorySet;
+
+pub(crate) use systems::{draw_bodies, snapshot_bodies, SnapshotBodies};
+/// Also added to headless apps to seek in their cycle
+pub use systems::{fast_forward, seek_level};
+
+pub mod prelude {
+    pub use super::data::*;
+    pub use super::events::*;
+    pub use super::TimelinePlugin;
+}
+
+/// Lets the player pause a cycle, look back at any earlier moment
+/// and resume recording from there
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SeekEvent>()
+            .add_event::<ForkEvent>()
+            .add_event::<RestartCycleEvent>()
+            .init_resource::<TimelineSnapshots>()
+            .init_resource::<TimelineView>()
+            .init_resource::<TimeScale>()
+            .add_systems(
+                OnEnter(GameState::Play),
+                (
+                    clear_snapshots,
+                    spawn_timeline_panel.after(setup_level),
+                    spawn_incident_text,
+                    spawn_status_bar,
+                ),
+            )
+            .add_systems(
+                OnExit(GameState::Play),
+                (stop_scrubbing, stop_fast_forward, reset_time_scale),
+            )
+            .add_systems(
+                FixedUpdate,
+                take_snapshot
+                    .before(LevelHistorySet::Replay)
+                    .run_if(in_state(GameState::Play)),
+            )
+            .add_systems(
+                Update,
+                (
+                    toggle_scrubbing.run_if(not(resource_exists::<FastForward>)),
+                    scrub_timeline.run_if(resource_exists::<Scrubbing>),
+                    restart_cycle_key.run_if(not(resource_exists::<Scrubbing>)),
+                    restart_cycle,
+                    fork_ghost,
+                    seek_level,
+                    fast_forward,
+                    show_fast_forward,
+                    show_scrubbed_bodies,
+                    draw_snapshot.run_if(resource_exists::<Scrubbing>),
+                    update_scrub_text,
+                )
+                    .chain()
+                    .run_if(in_state(PlayState::Running)),
+            )
+            // Also runs while paused, not to miss the modifications of the level
+            .add_systems(Update, restart_reloaded_level.before(restart_cycle))
+            .add_systems(
+                Update,
+                (
+                    control_timeline_view,
+                    move_timeline_cursor,
+                    show_incidents,
+                    show_history_usage,
+                    change_time_scale
+                        .run_if(not(resource_exists::<Scrubbing>))
+                        .run_if(in_state(PlayState::Running)),
+                    apply_time_scale,
+                    sync_timeline_markers,
+                    zoom_timeline_markers,
+                )
+                    .chain()
+                    .after(seek_level)
+                    .run_if(in_state(GameState::Play)),
+            );
+    }
+}
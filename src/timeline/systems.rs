@@ -0,0 +1,947 @@
+use bevy::app::FixedMain;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::utils::Instant;
+
+use super::prelude::*;
+use crate::character::prelude::SelectedCharacter;
+use crate::ennemy::prelude::*;
+use crate::game::{CurrentLevel, GameState, LevelBody, LevelSetup, PlayState};
+use crate::level_history::prelude::*;
+use crate::levels::prelude::*;
+use crate::player::prelude::*;
+
+/// The bodies of the level that are shown when looking back at a moment
+pub type SnapshotBodies<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        Has<Player>,
+        Option<&'static EnnemyKind>,
+        Option<&'static AttackProjectile>,
+        Option<&'static Team>,
+    ),
+    Or<(
+        With<Player>,
+        With<Ghost>,
+        With<Ennemy>,
+        With<AttackProjectile>,
+    )>,
+>;
+
+/// Where every body of the level is
+pub fn snapshot_bodies(bodies: &SnapshotBodies) -> Vec<BodySnapshot> {
+    bodies
+        .iter()
+        .map(|(transform, is_player, kind, projectile, team)| {
+            let kind = match (is_player, kind, projectile) {
+                (true, _, _) => BodyKind::Player,
+                (_, Some(kind), _) => BodyKind::Ennemy(*kind),
+                (_, _, Some(_)) => BodyKind::Projectile(team.copied().unwrap_or(Team::Enemy)),
+                _ => BodyKind::Ghost,
+            };
+            BodySnapshot {
+                kind,
+                position: transform.translation.truncate(),
+                rotation: transform.rotation,
+            }
+        })
+        .collect()
+}
+
+/// Draws the bodies of a snapshot, players and ghosts show where they face
+pub fn draw_bodies(gizmos: &mut Gizmos, bodies: &[BodySnapshot]) {
+    for body in bodies.iter() {
+        let color = body.kind.color();
+        let radius = body.kind.radius();
+        gizmos.circle_2d(body.position, radius, color);
+        if matches!(body.kind, BodyKind::Player | BodyKind::Ghost) {
+            let facing = (body.rotation * Vec3::X).truncate();
+            gizmos.line_2d(body.position, body.position + facing * radius * 1.5, color);
+        }
+    }
+}
+
+/// Records where every body of the level is every [`SNAPSHOT_INTERVAL`] ticks
+pub fn take_snapshot(
+    tick: Res<LevelTick>,
+    mut snapshots: ResMut<TimelineSnapshots>,
+    bodies: SnapshotBodies,
+) {
+    if tick.0 % SNAPSHOT_INTERVAL != 0 {
+        return;
+    }
+    let bodies = snapshot_bodies(&bodies);
+
+    let index = snapshots.0.partition_point(|s| s.tick < tick.0);
+    snapshots.0.truncate(index);
+    snapshots.0.push(Snapshot {
+        tick: tick.0,
+        bodies,
+    });
+}
+
+pub fn clear_snapshots(mut snapshots: ResMut<TimelineSnapshots>) {
+    snapshots.0.clear();
+}
+
+/// Pauses the cycle with T to scrub its timeline
+///
+/// Enter resumes the cycle from the scrubbed moment,
+/// T or Escape resumes it where it was paused.
+/// Clicking the lane of a ghost, or one of the [`FORK_KEYS`], takes it over from there
+pub fn toggle_scrubbing(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    tick: Res<LevelTick>,
+    scrubbing: Option<Res<Scrubbing>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut seek_event: EventWriter<SeekEvent>,
+    mut fork_event: EventWriter<ForkEvent>,
+    text: Query<Entity, With<ScrubText>>,
+    lanes: Query<(&Interaction, &TimelineLane), Changed<Interaction>>,
+    ghost_list: Res<PlayerGhostList>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(scrubbing) = scrubbing else {
+        if keyboard_input.just_pressed(KeyCode::KeyT) {
+            commands.insert_resource(Scrubbing {
+                tick: tick.0,
+                end: tick.0,
+            });
+            time.pause();
+            commands.spawn((
+                ScrubText,
+                StateScoped(GameState::Play),
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("Kalam-Light.ttf"),
+                        font_size: 24.0,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                }),
+            ));
+        }
+        return;
+    };
+
+    let resume = keyboard_input.just_pressed(KeyCode::Enter);
+    let cancel = keyboard_input.any_just_pressed([KeyCode::KeyT, KeyCode::Escape]);
+    let fork = FORK_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+        .map(GhostIdentifier)
+        .or_else(|| {
+            lanes
+                .iter()
+                .find(|(interaction, lane)| {
+                    **interaction == Interaction::Pressed && lane.0 != ghost_list.live()
+                })
+                .map(|(_, lane)| lane.0)
+        });
+    if !resume && !cancel && fork.is_none() {
+        return;
+    }
+    if let Some(ghost) = fork {
+        fork_event.send(ForkEvent {
+            ghost,
+            tick: scrubbing.tick,
+        });
+    } else if resume && scrubbing.tick < scrubbing.end {
+        seek_event.send(SeekEvent {
+            tick: scrubbing.tick,
+        });
+    }
+    commands.remove_resource::<Scrubbing>();
+    time.unpause();
+    for entity in text.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Stops scrubbing when the cycle ends while paused
+pub fn stop_scrubbing(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    commands.remove_resource::<Scrubbing>();
+    time.unpause();
+}
+
+/// Moves through the timeline with the arrow keys, holding shift moves faster
+pub fn scrub_timeline(keyboard_input: Res<ButtonInput<KeyCode>>, mut scrubbing: ResMut<Scrubbing>) {
+    let step = if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        SCRUB_STEP * 10
+    } else {
+        SCRUB_STEP
+    };
+    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        scrubbing.tick =
+            scrubbing.tick.saturating_sub(step) / SNAPSHOT_INTERVAL * SNAPSHOT_INTERVAL;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowRight) {
+        scrubbing.tick =
+            ((scrubbing.tick + step) / SNAPSHOT_INTERVAL * SNAPSHOT_INTERVAL).min(scrubbing.end);
+    }
+}
+
+/// Hides the bodies of the level while an earlier moment is shown
+pub fn show_scrubbed_bodies(
+    scrubbing: Option<Res<Scrubbing>>,
+    mut bodies: Query<
+        &mut Visibility,
+        Or<(
+            With<Player>,
+            With<Ghost>,
+            With<Ennemy>,
+            With<AttackProjectile>,
+        )>,
+    >,
+) {
+    let visibility = match scrubbing {
+        Some(scrubbing) if scrubbing.tick < scrubbing.end => Visibility::Hidden,
+        _ => Visibility::Inherited,
+    };
+    for mut body in bodies.iter_mut() {
+        if *body != visibility {
+            *body = visibility;
+        }
+    }
+}
+
+/// Draws the bodies of the snapshot of the scrubbed moment
+pub fn draw_snapshot(
+    scrubbing: Res<Scrubbing>,
+    snapshots: Res<TimelineSnapshots>,
+    mut gizmos: Gizmos,
+) {
+    if scrubbing.tick >= scrubbing.end {
+        return;
+    }
+    let Some(snapshot) = snapshots.at(scrubbing.tick) else {
+        return;
+    };
+    draw_bodies(&mut gizmos, &snapshot.bodies);
+}
+
+pub fn update_scrub_text(
+    scrubbing: Option<Res<Scrubbing>>,
+    mut text: Query<&mut Text, With<ScrubText>>,
+) {
+    let Some(scrubbing) = scrubbing else {
+        return;
+    };
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!(
+            "{:.2}s / {:.2}s    Left/Right: scrub    Enter: resume from here    1-9 or click a lane: take over a ghost from here    T: resume",
+            LevelTick(scrubbing.tick).as_secs(),
+            LevelTick(scrubbing.end).as_secs(),
+        );
+    }
+}
+
+/// Hands a ghost over to the live player, then resumes the cycle where it is taken over
+///
+/// Only one ghost can be taken over during a cycle
+pub fn fork_ghost(world: &mut World, mut reader: Local<ManualEventReader<ForkEvent>>) {
+    let Some(fork) = reader
+        .read(world.resource::<Events<ForkEvent>>())
+        .last()
+        .cloned()
+    else {
+        return;
+    };
+    let ghost_list = world.resource::<PlayerGhostList>();
+    let Some(class) = ghost_list
+        .get_ghost(fork.ghost)
+        .filter(|g| !g.muted)
+        .map(|g| g.class)
+    else {
+        warn!("Ghost {} cannot be taken over", fork.ghost.0 + 1);
+        return;
+    };
+    match ghost_list.forked {
+        Some(forked) if forked != fork.ghost => {
+            warn!(
+                "Ghost {} is already taken over during this cycle",
+                forked.0 + 1
+            );
+            return;
+        }
+        Some(_) => {}
+        None => {
+            let live_player = ghost_list.live();
+            let recorded_events = world.resource::<RecordedEvents>().clone();
+            recorded_events.discard_from(world, live_player, 0);
+            // Kept until the cycle ends, it is put back if the cycle is abandoned
+            let stash = recorded_events.stash_ghost(world, fork.ghost);
+            world.resource_mut::<ForkedRecords>().0 = Some(stash);
+        }
+    }
+
+    let mut ghost_list = world.resource_mut::<PlayerGhostList>();
+    let relabel = ghost_list.forked.is_none();
+    ghost_list.forked = Some(fork.ghost);
+    ghost_list.forked_at = fork.tick;
+    // The seek keeps the panel, the lane of the live player moves to the ghost
+    if relabel {
+        let panels = world
+            .query_filtered::<Entity, With<TimelinePanel>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for panel in panels {
+            world.entity_mut(panel).despawn_recursive();
+        }
+        world.run_system_once(spawn_timeline_panel);
+    }
+    world.resource_mut::<SelectedCharacter>().set(class);
+    world.send_event(SeekEvent { tick: fork.tick });
+    info!(
+        "Took over ghost {} {class:?} at tick {}",
+        fork.ghost.0 + 1,
+        fork.tick
+    );
+}
+
+/// Restarts the cycle with R
+pub fn restart_cycle_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut restart_event: EventWriter<RestartCycleEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        restart_event.send(RestartCycleEvent);
+    }
+}
+
+/// Restarts the cycle when the file of the level being played is modified
+///
+/// The restart waits for the cycle to run, the level is rebuilt
+/// from its new definition while the ghosts and their records are kept
+pub fn restart_reloaded_level(
+    mut reloaded_event: EventReader<LevelReloadedEvent>,
+    current_level: Res<CurrentLevel>,
+    play_state: Option<Res<State<PlayState>>>,
+    scrubbing: Option<Res<Scrubbing>>,
+    mut pending: Local<bool>,
+    mut restart_event: EventWriter<RestartCycleEvent>,
+) {
+    let Some(play_state) = play_state else {
+        reloaded_event.clear();
+        *pending = false;
+        return;
+    };
+    let current = current_level.0.as_ref().map(|l| l.id);
+    if reloaded_event.read().any(|e| Some(e.level) == current) {
+        *pending = true;
+    }
+    if *pending && *play_state.get() == PlayState::Running && scrubbing.is_none() {
+        *pending = false;
+        restart_event.send(RestartCycleEvent);
+    }
+}
+
+/// Seeks back to where the run of the live player started
+pub fn restart_cycle(
+    mut restart_event: EventReader<RestartCycleEvent>,
+    ghost_list: Res<PlayerGhostList>,
+    mut seek_event: EventWriter<SeekEvent>,
+) {
+    if restart_event.read().last().is_none() {
+        return;
+    }
+    let tick = if ghost_list.forked.is_some() {
+        ghost_list.forked_at
+    } else {
+        0
+    };
+    seek_event.send(SeekEvent { tick });
+    info!("Restarting the cycle from tick {tick}");
+}
+
+/// Rebuilds the level at the tick of a [`SeekEvent`]
+///
+/// Only the bodies of the level are set up again, then [`fast_forward`] simulates them
+/// from its start, replaying the ghosts and the remaining records of the live player,
+/// so every body is exactly where it was at that tick
+pub fn seek_level(world: &mut World, mut reader: Local<ManualEventReader<SeekEvent>>) {
+    let Some(tick) = reader
+        .read(world.resource::<Events<SeekEvent>>())
+        .last()
+        .map(|e| e.tick)
+    else {
+        return;
+    };
+
+    let live_player = world.resource::<PlayerGhostList>().live();
+    world
+        .resource::<RecordedEvents>()
+        .clone()
+        .discard_from(world, live_player, tick);
+
+    let bodies = world
+        .query_filtered::<Entity, With<LevelBody>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in bodies {
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+    world.run_schedule(LevelSetup);
+
+    world.insert_resource(FastForward { target: tick });
+    world.resource_mut::<Time<Virtual>>().pause();
+}
+
+/// Simulates the ticks of a seek that fit in a frame,
+/// then resumes the cycle once the sought tick is reached
+pub fn fast_forward(world: &mut World) {
+    let Some(FastForward { target }) = world.get_resource::<FastForward>().copied() else {
+        return;
+    };
+    let start = Instant::now();
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    for _ in 0..FAST_FORWARD_STEPS {
+        if world.resource::<LevelTick>().0 >= target || start.elapsed() > FAST_FORWARD_BUDGET {
+            break;
+        }
+        world.run_schedule(FixedMain);
+    }
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+
+    let tick = world.resource::<LevelTick>().0;
+    if tick >= target {
+        world.remove_resource::<FastForward>();
+        world.resource_mut::<Time<Virtual>>().unpause();
+        info!("Resumed the cycle at tick {tick}");
+    }
+}
+
+/// Shows how far the level is simulated while it is fast-forwarded
+pub fn show_fast_forward(
+    mut commands: Commands,
+    fast_forward: Option<Res<FastForward>>,
+    tick: Res<LevelTick>,
+    mut text: Query<(Entity, &mut Text), With<FastForwardText>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(FastForward { target }) = fast_forward.as_deref().copied() else {
+        for (entity, _) in text.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+    let value = format!(
+        "Resuming at {:.2}s... {}%",
+        LevelTick(target).as_secs(),
+        tick.0 * 100 / target.max(1)
+    );
+    if text.is_empty() {
+        commands.spawn((
+            FastForwardText,
+            StateScoped(GameState::Play),
+            TextBundle::from_section(
+                value,
+                TextStyle {
+                    font: asset_server.load("Kalam-Light.ttf"),
+                    font_size: 24.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            }),
+        ));
+        return;
+    }
+    for (_, mut text) in text.iter_mut() {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+/// Stops simulating a seek when the cycle ends
+pub fn stop_fast_forward(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    commands.remove_resource::<FastForward>();
+    time.unpause();
+}
+
+/// Spawns the timeline panel, with one lane per ghost and one for the live player
+/// unless it took over a ghost
+pub fn spawn_timeline_panel(
+    mut commands: Commands,
+    ghost_list: Res<PlayerGhostList>,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("Kalam-Light.ttf");
+    let labels = ghost_list
+        .ghosts
+        .iter()
+        .enumerate()
+        .map(|(index, ghost)| {
+            // Muted ghosts keep their lane, their records are still in the history
+            let color = if ghost.muted {
+                Color::srgba(1.0, 1.0, 1.0, 0.4)
+            } else {
+                Color::WHITE
+            };
+            if ghost_list.forked == Some(GhostIdentifier(index)) {
+                (format!("{} You", index + 1), Color::WHITE)
+            } else {
+                (format!("{} {:?}", index + 1, ghost.class), color)
+            }
+        })
+        .chain(
+            ghost_list
+                .forked
+                .is_none()
+                .then(|| ("You".to_string(), Color::WHITE)),
+        );
+
+    commands
+        .spawn((
+            TimelinePanel,
+            StateScoped(GameState::Play),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..default()
+            },
+        ))
+        .with_children(|panel| {
+            for (index, (label, color)) in labels.enumerate() {
+                let background = if index % 2 == 0 {
+                    Color::srgba(1.0, 1.0, 1.0, 0.05)
+                } else {
+                    Color::NONE
+                };
+                panel
+                    .spawn((
+                        TimelineLane(GhostIdentifier(index)),
+                        Interaction::default(),
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Px(LANE_HEIGHT),
+                                ..default()
+                            },
+                            background_color: background.into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|lane| {
+                        lane.spawn(
+                            TextBundle::from_section(
+                                label,
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: 14.0,
+                                    color,
+                                },
+                            )
+                            .with_style(Style {
+                                width: Val::Px(LANE_LABEL_WIDTH),
+                                padding: UiRect::left(Val::Px(5.0)),
+                                ..default()
+                            }),
+                        );
+                        lane.spawn((
+                            TimelineTrack,
+                            NodeBundle {
+                                style: Style {
+                                    flex_grow: 1.0,
+                                    height: Val::Percent(100.0),
+                                    overflow: Overflow::clip(),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ))
+                        .with_children(|track| {
+                            track
+                                .spawn((
+                                    TimelineStrip(GhostIdentifier(index)),
+                                    NodeBundle {
+                                        style: Style {
+                                            position_type: PositionType::Absolute,
+                                            height: Val::Percent(100.0),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                ))
+                                .with_children(|strip| {
+                                    strip.spawn((
+                                        TimelineCursor,
+                                        NodeBundle {
+                                            style: Style {
+                                                position_type: PositionType::Absolute,
+                                                width: Val::Px(1.0),
+                                                height: Val::Percent(100.0),
+                                                ..default()
+                                            },
+                                            background_color: Color::srgba(1.0, 1.0, 0.0, 0.8)
+                                                .into(),
+                                            z_index: ZIndex::Local(1),
+                                            ..default()
+                                        },
+                                    ));
+                                });
+                        });
+                    });
+            }
+        });
+}
+
+/// Keeps the markers of every recorded event type in step with its history
+///
+/// While a cycle is played only the new records of the live player get a marker,
+/// all the markers of a type are rebuilt when records are removed or the panel is respawned
+pub fn sync_timeline_markers(
+    world: &World,
+    mut commands: Commands,
+    ghost_list: Res<PlayerGhostList>,
+    recorded_events: Res<RecordedEvents>,
+    tick: Res<LevelTick>,
+    view: Res<TimelineView>,
+    strips: Query<(Entity, &TimelineStrip)>,
+    new_strips: Query<(), Added<TimelineStrip>>,
+    mut syncs: Local<Vec<MarkerSync>>,
+) {
+    syncs.resize_with(recorded_events.len(), MarkerSync::default);
+    let live_player = ghost_list.live();
+    let mut lanes = vec![None; ghost_list.ghosts.len() + 1];
+    for (entity, strip) in strips.iter() {
+        if let Some(lane) = lanes.get_mut(strip.0 .0) {
+            *lane = Some(entity);
+        }
+    }
+
+    let mut records = vec![];
+    for (index, sync) in syncs.iter_mut().enumerate() {
+        let state = recorded_events.state(world, index);
+        let rebuild = !new_strips.is_empty()
+            || sync.state.map_or(true, |synced| {
+                synced.added != state.added || synced.revision != state.revision
+            });
+        if !rebuild && sync.state == Some(state) {
+            continue;
+        }
+
+        records.clear();
+        if rebuild {
+            for marker in sync.markers.drain(..) {
+                if let Some(marker) = commands.get_entity(marker) {
+                    marker.despawn_recursive();
+                }
+            }
+            recorded_events.markers(world, index, None, &mut records);
+        } else {
+            recorded_events.markers(
+                world,
+                index,
+                Some((live_player, sync.next_tick)),
+                &mut records,
+            );
+        }
+        sync.state = Some(state);
+        sync.next_tick = tick.0;
+
+        for record in records.iter() {
+            let Some(strip) = lanes.get(record.ghost.0).copied().flatten() else {
+                continue;
+            };
+            let marker = TimelineMarker {
+                tick: record.tick,
+                width: record.size.x,
+            };
+            let entity = commands
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: marker.left(&view),
+                            top: Val::Px((LANE_HEIGHT - record.size.y) / 2.0),
+                            width: Val::Px(record.size.x),
+                            height: Val::Px(record.size.y),
+                            ..default()
+                        },
+                        background_color: record.color.into(),
+                        ..default()
+                    },
+                    marker,
+                ))
+                .set_parent(strip)
+                .id();
+            sync.markers.push(entity);
+        }
+    }
+}
+
+/// Zooms the timeline with - and =, pans it with [ and ]
+///
+/// Panning stops following the current tick, \ follows it again
+pub fn control_timeline_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    mut view: ResMut<TimelineView>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Equal) {
+        view.pixels_per_tick = (view.pixels_per_tick * 2.0).min(10.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        view.pixels_per_tick = (view.pixels_per_tick / 2.0).max(0.005);
+    }
+
+    let pan = 600.0 * time.delta_seconds() / view.pixels_per_tick;
+    if keyboard_input.pressed(KeyCode::BracketLeft) {
+        view.start = (view.start - pan).max(0.0);
+        view.follow = false;
+    }
+    if keyboard_input.pressed(KeyCode::BracketRight) {
+        view.start += pan;
+        view.follow = false;
+    }
+    if keyboard_input.just_pressed(KeyCode::Backslash) {
+        view.follow = true;
+    }
+}
+
+/// Moves the cursor and the lanes of the timeline to the shown tick
+pub fn move_timeline_cursor(
+    tick: Res<LevelTick>,
+    scrubbing: Option<Res<Scrubbing>>,
+    mut view: ResMut<TimelineView>,
+    tracks: Query<&Node, With<TimelineTrack>>,
+    mut strips: Query<&mut Style, (With<TimelineStrip>, Without<TimelineCursor>)>,
+    mut cursors: Query<&mut Style, (With<TimelineCursor>, Without<TimelineStrip>)>,
+) {
+    let shown = scrubbing.map_or(tick.0, |s| s.tick) as f32;
+    if view.follow {
+        let visible = tracks.iter().next().map_or(0.0, |node| node.size().x) / view.pixels_per_tick;
+        let start = (shown - visible * 0.75).max(0.0);
+        if view.start != start {
+            view.start = start;
+        }
+    }
+
+    for mut style in strips.iter_mut() {
+        style.left = Val::Px(-view.start * view.pixels_per_tick);
+    }
+    for mut style in cursors.iter_mut() {
+        style.left = Val::Px(shown * view.pixels_per_tick);
+    }
+}
+
+/// Places the markers again when the timeline is zoomed
+pub fn zoom_timeline_markers(
+    view: Res<TimelineView>,
+    mut markers: Query<(&TimelineMarker, &mut Style)>,
+    mut pixels_per_tick: Local<f32>,
+) {
+    if *pixels_per_tick == view.pixels_per_tick {
+        return;
+    }
+    *pixels_per_tick = view.pixels_per_tick;
+    for (marker, mut style) in markers.iter_mut() {
+        style.left = marker.left(&view);
+    }
+}
+
+pub fn spawn_incident_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        IncidentText,
+        StateScoped(GameState::Play),
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("Kalam-Light.ttf"),
+                font_size: 20.0,
+                color: Color::srgb(1.0, 0.5, 0.4),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+    ));
+}
+
+/// Spawns the speed of the cycle and the size of the histories at the bottom of the screen
+pub fn spawn_status_bar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load("Kalam-Light.ttf"),
+        font_size: 14.0,
+        color: Color::srgba(1.0, 1.0, 1.0, 0.6),
+    };
+    commands
+        .spawn((
+            StateScoped(GameState::Play),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(20.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|bar| {
+            bar.spawn((TimeScaleText, TextBundle::from_section("", style.clone())));
+            bar.spawn((HistoryUsageText, TextBundle::from_section("", style)));
+        });
+}
+
+/// Slows down the cycle with comma, speeds it up with period and slash plays it at normal speed
+pub fn change_time_scale(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    let index = if keyboard_input.just_pressed(KeyCode::Comma) {
+        time_scale.0.saturating_sub(1)
+    } else if keyboard_input.just_pressed(KeyCode::Period) {
+        (time_scale.0 + 1).min(TIME_SCALES.len() - 1)
+    } else if keyboard_input.just_pressed(KeyCode::Slash) {
+        TimeScale::default().0
+    } else {
+        return;
+    };
+    time_scale.set_if_neq(TimeScale(index));
+}
+
+/// Plays the virtual clock, and so the fixed ticks, at the speed of the cycle
+pub fn apply_time_scale(
+    time_scale: Res<TimeScale>,
+    new_text: Query<(), Added<TimeScaleText>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut text: Query<&mut Text, With<TimeScaleText>>,
+) {
+    if !time_scale.is_changed() && new_text.is_empty() {
+        return;
+    }
+    time.set_relative_speed_f64(time_scale.speed());
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!("Speed x{}", time_scale.speed());
+    }
+}
+
+/// Menus and playbacks are not slowed down by the speed of the cycles
+pub fn reset_time_scale(mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(1.0);
+}
+
+/// Shows how many records the histories hold and their memory, to size long levels
+pub fn show_history_usage(
+    usage: Res<HistoryUsage>,
+    new_text: Query<(), Added<HistoryUsageText>>,
+    mut text: Query<&mut Text, With<HistoryUsageText>>,
+) {
+    if !usage.is_changed() && new_text.is_empty() {
+        return;
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = usage.to_string();
+    }
+}
+
+/// Lists the ghosts that went off-script or died, and when, and marks it on their lane
+pub fn show_incidents(
+    mut commands: Commands,
+    divergences: Res<GhostDivergences>,
+    casualties: Res<GhostCasualties>,
+    ghost_list: Res<PlayerGhostList>,
+    view: Res<TimelineView>,
+    strips: Query<(Entity, &TimelineStrip)>,
+    new_strips: Query<(), Added<TimelineStrip>>,
+    markers: Query<Entity, With<IncidentMarker>>,
+    mut text: Query<&mut Text, With<IncidentText>>,
+) {
+    if !divergences.is_changed() && !casualties.is_changed() && new_strips.is_empty() {
+        return;
+    }
+    let name = |ghost: GhostIdentifier| {
+        let class = ghost_list
+            .get_ghost(ghost)
+            .map(|g| format!("{:?}", g.class))
+            .unwrap_or_default();
+        format!("{} {class}", ghost.0 + 1)
+    };
+    let divergence_lines = divergences.0.iter().map(|d| {
+        let secs = LevelTick(d.tick).as_secs();
+        if d.distance.is_finite() {
+            format!(
+                "{} off-script at {secs:.1}s ({:.0} px)",
+                name(d.ghost),
+                d.distance
+            )
+        } else {
+            format!("{} killed early at {secs:.1}s", name(d.ghost))
+        }
+    });
+    let casualty_lines = casualties.0.iter().map(|c| {
+        let secs = LevelTick(c.tick).as_secs();
+        format!("{} {} at {secs:.1}s", name(c.ghost), c.killer)
+    });
+    let lines = divergence_lines.chain(casualty_lines).collect::<Vec<_>>();
+    for mut text in text.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+
+    for marker in markers.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+    let incidents = divergences
+        .0
+        .iter()
+        .map(|d| (d.ghost, d.tick, Color::srgb(1.0, 0.5, 0.0)))
+        .chain(
+            casualties
+                .0
+                .iter()
+                .map(|c| (c.ghost, c.tick, Color::srgb(1.0, 0.1, 0.1))),
+        );
+    for (ghost, tick, color) in incidents {
+        let Some((strip, _)) = strips.iter().find(|(_, s)| s.0 == ghost) else {
+            continue;
+        };
+        let marker = TimelineMarker { tick, width: 4.0 };
+        commands
+            .spawn((
+                IncidentMarker,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: marker.left(&view),
+                        width: Val::Px(marker.width),
+                        height: Val::Px(LANE_HEIGHT),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    z_index: ZIndex::Local(1),
+                    ..default()
+                },
+                marker,
+            ))
+            .set_parent(strip);
+    }
+}
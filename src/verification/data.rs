@@ -0,0 +1,92 @@
+use std::fmt;
+
+use crate::ennemy::prelude::*;
+use crate::level_history::prelude::*;
+
+/// An ennemy killed while verifying a run
+#[derive(Debug, Clone)]
+pub struct KillReport {
+    pub tick: u64,
+    pub ennemy: EnnemyKind,
+    /// The ghost that shot the projectile, if it was one
+    pub ghost: Option<GhostIdentifier>,
+}
+
+/// Outcome of the headless simulation of a saved run
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    pub level: usize,
+    /// The score recorded by the level completion, if the level was completed
+    pub cycles: Option<usize>,
+    /// The number of simulated ticks
+    pub ticks: u64,
+    pub kills: Vec<KillReport>,
+    /// The first divergence of each ghost from its recording
+    pub divergences: Vec<GhostDivergedEvent>,
+    pub casualties: Vec<GhostCasualty>,
+    /// How much the loaded histories hold
+    pub usage: HistoryUsage,
+}
+
+impl RunReport {
+    pub fn completed(&self) -> bool {
+        self.cycles.is_some()
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cycles {
+            Some(cycles) => writeln!(f, "Level {} completed in {cycles} cycles", self.level)?,
+            None => writeln!(f, "Level {} not completed", self.level)?,
+        }
+        writeln!(
+            f,
+            "Simulated {} ticks ({:.2}s)",
+            self.ticks,
+            LevelTick(self.ticks).as_secs()
+        )?;
+        writeln!(f, "History of {}", self.usage)?;
+        for kill in self.kills.iter() {
+            let killer = kill
+                .ghost
+                .map(|g| format!("ghost {}", g.0 + 1))
+                .unwrap_or_else(|| "not a ghost".to_string());
+            writeln!(
+                f,
+                "  tick {:>6}: {:?} killed by {killer}",
+                kill.tick, kill.ennemy
+            )?;
+        }
+        for casualty in self.casualties.iter() {
+            writeln!(
+                f,
+                "  tick {:>6}: ghost {} {} at ({:.0}, {:.0})",
+                casualty.tick,
+                casualty.ghost.0 + 1,
+                casualty.killer,
+                casualty.position.x,
+                casualty.position.y
+            )?;
+        }
+        for divergence in self.divergences.iter() {
+            if divergence.distance.is_finite() {
+                writeln!(
+                    f,
+                    "  tick {:>6}: ghost {} diverged by {:.1} px",
+                    divergence.tick,
+                    divergence.ghost.0 + 1,
+                    divergence.distance
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "  tick {:>6}: ghost {} killed before the end of its recording",
+                    divergence.tick,
+                    divergence.ghost.0 + 1
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
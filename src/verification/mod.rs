@@ -0,0 +1,144 @@
+mod data;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy::transform::TransformPlugin;
+use data::*;
+
+use crate::ennemy::prelude::*;
+use crate::game::{CurrentLevel, GameState, LevelCompletedEvent, Levels};
+use crate::level_history::prelude::*;
+use crate::SimulationPlugin;
+
+pub mod prelude {
+    pub use super::data::*;
+    pub use super::{headless_app, load_run, verify_run};
+}
+
+/// Ticks simulated after the last recorded event before the level is considered failed
+pub const GRACE_TICKS: u64 = 10 * TICK_RATE as u64;
+
+/// An app simulating the levels without window, rendering nor input devices
+///
+/// Every update advances the simulation by exactly one tick
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        TransformPlugin,
+        HierarchyPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        InputPlugin,
+        SimulationPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_asset::<Image>()
+    .init_asset::<TextureAtlasLayout>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / TICK_RATE,
+    )));
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Loads the ghosts of a saved run in a [`headless_app`] and starts playing its level
+///
+/// Returns the tick of the last recorded event
+pub fn load_run(app: &mut App, file: &ReplayFile) -> Result<u64, ReplayError> {
+    while *app.world().resource::<State<GameState>>() == GameState::Loading {
+        app.update();
+    }
+    let world = app.world_mut();
+
+    let level = world
+        .resource::<Levels>()
+        .iter()
+        .find(|l| l.id == file.level)
+        .cloned()
+        .ok_or(ReplayError::UnknownLevel(file.level))?;
+    // Selects the level first, like the level selection does,
+    // so the level change does not clear the loaded histories
+    world.insert_resource(CurrentLevel(Some(level)));
+    app.update();
+
+    let world = app.world_mut();
+    let last_tick = world
+        .resource::<RecordedEvents>()
+        .clone()
+        .insert_histories(file, world)?;
+    world.insert_resource(file.ghost_list());
+    world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Play);
+    Ok(last_tick)
+}
+
+/// Plays the ghosts of a saved run until the level is completed
+/// or nothing happened for [`GRACE_TICKS`] after their last event
+pub fn verify_run(file: &ReplayFile) -> Result<RunReport, ReplayError> {
+    let mut app = headless_app();
+    let last_tick = load_run(&mut app, file)?;
+    let world = app.world_mut();
+
+    let mut killed_reader = world.resource::<Events<EnnemyKilledEvent>>().get_reader();
+    let mut completed_reader = world.resource::<Events<LevelCompletedEvent>>().get_reader();
+    let mut ghosts = HashMap::new();
+    let mut report = RunReport {
+        level: file.level,
+        cycles: None,
+        ticks: 0,
+        kills: vec![],
+        divergences: vec![],
+        casualties: vec![],
+        usage: world.resource::<RecordedEvents>().usage(world),
+    };
+
+    while report.ticks <= last_tick + GRACE_TICKS {
+        app.update();
+        let world = app.world();
+        report.ticks = world.resource::<LevelTick>().0;
+
+        // Ghosts are despawned when killed, so their entities are kept as soon as they spawn
+        for (index, ghost) in world
+            .resource::<PlayerGhostList>()
+            .ghosts
+            .iter()
+            .enumerate()
+        {
+            if let Some(entity) = ghost.entity {
+                ghosts.insert(entity, GhostIdentifier(index));
+            }
+        }
+
+        for kill in killed_reader.read(world.resource::<Events<EnnemyKilledEvent>>()) {
+            report.kills.push(KillReport {
+                tick: report.ticks,
+                ennemy: kill.kind,
+                ghost: ghosts.get(&kill.killer).copied(),
+            });
+        }
+
+        if let Some(completed) = completed_reader
+            .read(world.resource::<Events<LevelCompletedEvent>>())
+            .last()
+        {
+            report.cycles = Some(completed.cycles);
+            break;
+        }
+    }
+    report.divergences = app.world().resource::<GhostDivergences>().0.clone();
+    report.casualties = app.world().resource::<GhostCasualties>().0.clone();
+
+    Ok(report)
+}
@@ -1,9 +1,241 @@
-use bevy::prelude::*;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use avian2d::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+use serde::{Deserialize, Serialize};
 
 pub const WALL_WIDTH: f32 = 20.0;
 
+/// Number of points of the outline of a round wall
+const ROUND_SEGMENTS: usize = 24;
+
 /// Marker component of the walls.
 ///
 /// This component allow to identify the walls during Bevy queries.
 #[derive(Component, Debug)]
 pub struct Wall;
+
+/// A wall of a level, as designers write it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WallShape {
+    Rectangle {
+        /// Centre of the wall
+        position: Vec2,
+        size: Vec2,
+        /// Counterclockwise, in radians
+        #[serde(default)]
+        rotation: f32,
+    },
+    /// Fills the convex hull of the points
+    Polygon {
+        points: Vec<Vec2>,
+    },
+    Circle {
+        position: Vec2,
+        radius: f32,
+    },
+    /// A line of cover going through the points, with round ends
+    Polyline {
+        points: Vec<Vec2>,
+        #[serde(default = "wall_width")]
+        width: f32,
+    },
+}
+
+fn wall_width() -> f32 {
+    WALL_WIDTH
+}
+
+/// One body of a wall, a polyline has one for each of its segments
+pub struct WallPart {
+    pub position: Vec2,
+    /// Counterclockwise, in radians
+    pub rotation: f32,
+    pub collider: Collider,
+}
+
+impl WallPart {
+    /// The mesh drawing the body, only built for the walls that are spawned
+    pub fn mesh(&self) -> Mesh {
+        let shape = self.collider.shape();
+        if let Some(cuboid) = shape.as_cuboid() {
+            Rectangle::new(cuboid.half_extents.x * 2.0, cuboid.half_extents.y * 2.0).into()
+        } else if let Some(ball) = shape.as_ball() {
+            Circle::new(ball.radius).into()
+        } else if let Some(capsule) = shape.as_capsule() {
+            Capsule2d::new(capsule.radius, capsule.segment.length()).into()
+        } else if let Some(polygon) = shape.as_convex_polygon() {
+            let points: Vec<Vec2> = polygon
+                .points()
+                .iter()
+                .map(|p| Vec2::new(p.x, p.y))
+                .collect();
+            polygon_mesh(&points)
+        } else {
+            Rectangle::default().into()
+        }
+    }
+}
+
+impl WallShape {
+    /// The bodies of the wall, none for a wall without area
+    pub fn parts(&self) -> Vec<WallPart> {
+        match self {
+            WallShape::Rectangle {
+                position,
+                size,
+                rotation,
+            } => vec![WallPart {
+                position: *position,
+                rotation: *rotation,
+                collider: Collider::rectangle(size.x, size.y),
+            }],
+            WallShape::Polygon { points } => {
+                let centre = self.position();
+                let local = points.iter().map(|p| *p - centre).collect();
+                let Some(collider) = Collider::convex_hull(local) else {
+                    return vec![];
+                };
+                vec![WallPart {
+                    position: centre,
+                    rotation: 0.0,
+                    collider,
+                }]
+            }
+            WallShape::Circle { position, radius } => vec![WallPart {
+                position: *position,
+                rotation: 0.0,
+                collider: Collider::circle(*radius),
+            }],
+            WallShape::Polyline { points, width } => points
+                .windows(2)
+                .filter(|segment| segment[0] != segment[1])
+                .map(|segment| {
+                    let direction = segment[1] - segment[0];
+                    let length = direction.length();
+                    // The capsules are along Y
+                    WallPart {
+                        position: segment[0].midpoint(segment[1]),
+                        rotation: direction.to_angle() - FRAC_PI_2,
+                        collider: Collider::capsule(width / 2.0, length),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Centre of the wall, the mean of the points of polygons and polylines
+    pub fn position(&self) -> Vec2 {
+        match self {
+            WallShape::Rectangle { position, .. } | WallShape::Circle { position, .. } => *position,
+            WallShape::Polygon { points } | WallShape::Polyline { points, .. } => {
+                points.iter().sum::<Vec2>() / points.len().max(1) as f32
+            }
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vec2) {
+        match self {
+            WallShape::Rectangle { position, .. } | WallShape::Circle { position, .. } => {
+                *position += offset
+            }
+            WallShape::Polygon { points } | WallShape::Polyline { points, .. } => {
+                points.iter_mut().for_each(|p| *p += offset)
+            }
+        }
+    }
+
+    /// Turns the wall counterclockwise around its centre
+    pub fn rotate(&mut self, angle: f32) {
+        let centre = self.position();
+        match self {
+            WallShape::Rectangle { rotation, .. } => *rotation = (*rotation + angle) % TAU,
+            WallShape::Circle { .. } => {}
+            WallShape::Polygon { points } | WallShape::Polyline { points, .. } => {
+                let rotation = Rot2::radians(angle);
+                points
+                    .iter_mut()
+                    .for_each(|p| *p = centre + rotation * (*p - centre));
+            }
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.parts().iter().any(|part| {
+            part.collider.contains_point(
+                Position(part.position),
+                Rotation::radians(part.rotation),
+                point,
+            )
+        })
+    }
+
+    /// Outlines of the bodies of the wall, in the world
+    pub fn outlines(&self) -> Vec<Vec<Vec2>> {
+        self.parts()
+            .iter()
+            .map(|part| collider_outline(&part.collider, part.position, part.rotation))
+            .collect()
+    }
+}
+
+/// Triangles fanning from the first point of a convex polygon
+fn polygon_mesh(points: &[Vec2]) -> Mesh {
+    let positions: Vec<[f32; 3]> = points.iter().map(|p| [p.x, p.y, 0.0]).collect();
+    let indices = (1..points.len().saturating_sub(1) as u32)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; points.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; points.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn arc(centre: Vec2, radius: f32, start: f32, angle: f32, segments: usize) -> Vec<Vec2> {
+    (0..=segments)
+        .map(|i| centre + Vec2::from_angle(start + angle * i as f32 / segments as f32) * radius)
+        .collect()
+}
+
+/// Closed outline of a wall collider placed in the world, empty for the shapes walls do not use
+pub fn collider_outline(collider: &Collider, position: Vec2, rotation: f32) -> Vec<Vec2> {
+    let shape = collider.shape();
+    let local = if let Some(cuboid) = shape.as_cuboid() {
+        let half = Vec2::new(cuboid.half_extents.x, cuboid.half_extents.y);
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| Vec2::new(x, y) * half)
+            .to_vec()
+    } else if let Some(ball) = shape.as_ball() {
+        let mut points = arc(Vec2::ZERO, ball.radius, 0.0, TAU, ROUND_SEGMENTS);
+        points.pop();
+        points
+    } else if let Some(capsule) = shape.as_capsule() {
+        let a = Vec2::new(capsule.segment.a.x, capsule.segment.a.y);
+        let b = Vec2::new(capsule.segment.b.x, capsule.segment.b.y);
+        let start = (b - a).to_angle() - FRAC_PI_2;
+        let segments = ROUND_SEGMENTS / 2;
+        let mut points = arc(b, capsule.radius, start, PI, segments);
+        points.extend(arc(a, capsule.radius, start + PI, PI, segments));
+        points
+    } else if let Some(polygon) = shape.as_convex_polygon() {
+        polygon
+            .points()
+            .iter()
+            .map(|p| Vec2::new(p.x, p.y))
+            .collect()
+    } else {
+        vec![]
+    };
+    let rotation = Rot2::radians(rotation);
+    local.into_iter().map(|p| position + rotation * p).collect()
+}
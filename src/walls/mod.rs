@@ -4,7 +4,7 @@ mod systems;
 use bevy::prelude::*;
 use systems::*;
 
-use crate::game::GameState;
+use crate::game::LevelSetup;
 
 pub mod prelude {
     pub use super::data::*;
@@ -15,6 +15,6 @@ pub struct WallPlugin;
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Play), spawn_walls);
+        app.add_systems(LevelSetup, spawn_walls);
     }
 }
@@ -1,4 +1,5 @@
-use crate::game::GameState;
+use crate::game::{CurrentLevel, GameState, LevelBody};
+use crate::levels::prelude::*;
 
 use super::prelude::*;
 use avian2d::prelude::*;
@@ -6,79 +7,67 @@ use bevy::prelude::*;
 
 /// Spawn the walls to the map.
 ///
-/// Allow to spawn all the walls of the current level.
+/// Allow to spawn all the walls of the current level,
+/// the borders of its arena and the walls inside it.
 pub fn spawn_walls(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    current_level: Res<CurrentLevel>,
+    definitions: Res<Assets<LevelDefinition>>,
 ) {
-    let level_height = 600.0;
-    let level_width = 1000.0;
+    let Some(level) = current_level.definition(&definitions) else {
+        return;
+    };
+    let level_width = level.arena.x;
+    let level_height = level.arena.y;
 
-    // Left wall
-    commands.spawn((
-        Wall,
-        StateScoped(GameState::Play),
-        ColorMesh2dBundle {
-            mesh: meshes
-                .add(Rectangle::from_size(Vec2::new(WALL_WIDTH, level_height)))
-                .into(),
-            material: materials.add(Color::linear_rgb(0.3, 0.3, 0.3)),
-            transform: Transform::from_xyz(level_width / -2.0 - WALL_WIDTH / 2.0, 0.0, 0.0),
-            ..default()
+    let borders = [
+        // Left wall
+        WallShape::Rectangle {
+            position: Vec2::new(level_width / -2.0 - WALL_WIDTH / 2.0, 0.0),
+            size: Vec2::new(WALL_WIDTH, level_height),
+            rotation: 0.0,
         },
-        RigidBody::Static,
-        Collider::rectangle(WALL_WIDTH, level_height),
-    ));
-
-    // Right wall
-    commands.spawn((
-        Wall,
-        ColorMesh2dBundle {
-            mesh: meshes
-                .add(Rectangle::from_size(Vec2::new(WALL_WIDTH, level_height)))
-                .into(),
-            material: materials.add(Color::linear_rgb(0.3, 0.3, 0.3)),
-            transform: Transform::from_xyz(level_width / 2.0 + WALL_WIDTH / 2.0, 0.0, 0.0),
-            ..default()
+        // Right wall
+        WallShape::Rectangle {
+            position: Vec2::new(level_width / 2.0 + WALL_WIDTH / 2.0, 0.0),
+            size: Vec2::new(WALL_WIDTH, level_height),
+            rotation: 0.0,
         },
-        RigidBody::Static,
-        Collider::rectangle(WALL_WIDTH, level_height),
-    ));
-
-    // Top wall
-    commands.spawn((
-        Wall,
-        ColorMesh2dBundle {
-            mesh: meshes
-                .add(Rectangle::from_size(Vec2::new(
-                    level_width + WALL_WIDTH * 2.0,
-                    WALL_WIDTH,
-                )))
-                .into(),
-            material: materials.add(Color::linear_rgb(0.3, 0.3, 0.3)),
-            transform: Transform::from_xyz(0.0, level_height / -2.0 - WALL_WIDTH / 2.0, 0.0),
-            ..default()
+        // Top wall
+        WallShape::Rectangle {
+            position: Vec2::new(0.0, level_height / -2.0 - WALL_WIDTH / 2.0),
+            size: Vec2::new(level_width + WALL_WIDTH * 2.0, WALL_WIDTH),
+            rotation: 0.0,
         },
-        RigidBody::Static,
-        Collider::rectangle(level_width + WALL_WIDTH * 2.0, WALL_WIDTH),
-    ));
-
-    // Bottom wall
-    commands.spawn((
-        Wall,
-        ColorMesh2dBundle {
-            mesh: meshes
-                .add(Rectangle::from_size(Vec2::new(
-                    level_width + WALL_WIDTH * 2.0,
-                    WALL_WIDTH,
-                )))
-                .into(),
-            material: materials.add(Color::linear_rgb(0.3, 0.3, 0.3)),
-            transform: Transform::from_xyz(0.0, level_height / 2.0 + WALL_WIDTH / 2.0, 0.0),
-            ..default()
+        // Bottom wall
+        WallShape::Rectangle {
+            position: Vec2::new(0.0, level_height / 2.0 + WALL_WIDTH / 2.0),
+            size: Vec2::new(level_width + WALL_WIDTH * 2.0, WALL_WIDTH),
+            rotation: 0.0,
         },
-        RigidBody::Static,
-        Collider::rectangle(level_width + WALL_WIDTH * 2.0, WALL_WIDTH),
-    ));
+    ];
+
+    let material = materials.add(Color::linear_rgb(0.3, 0.3, 0.3));
+    for part in borders
+        .iter()
+        .chain(level.walls.iter())
+        .flat_map(WallShape::parts)
+    {
+        commands.spawn((
+            Wall,
+            LevelBody,
+            StateScoped(GameState::Play),
+            ColorMesh2dBundle {
+                mesh: meshes.add(part.mesh()).into(),
+                material: material.clone(),
+                transform: Transform::from_translation(part.position.extend(0.0))
+                    .with_rotation(Quat::from_rotation_z(part.rotation)),
+                ..default()
+            },
+            RigidBody::Static,
+            part.collider,
+        ));
+    }
 }
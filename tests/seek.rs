@@ -0,0 +1,112 @@
+//! Seeks in a cycle of the first level played by a headless app
+
+use bevy::prelude::*;
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::player::prelude::*;
+use cycle_of_the_fallen::timeline::prelude::*;
+use cycle_of_the_fallen::timeline::{fast_forward, seek_level};
+use cycle_of_the_fallen::verification::prelude::*;
+
+/// The tick the ghost dies at, at the end of its recording
+const DEATH: u64 = 100;
+
+/// A file with a single ranger ghost walking up until it dies
+fn dying_ranger() -> ReplayFile {
+    let mut actions = LevelHistory::default();
+    actions.push(EventRecord {
+        ghost: GhostIdentifier(0),
+        tick: 1,
+        event: PlayerActionEvent {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+            action: PlayerAction {
+                movement: Vec2::Y,
+                aim: Vec2::Y,
+                attack: false,
+                ability: false,
+            },
+        },
+    });
+    let mut killed = LevelHistory::default();
+    killed.push(EventRecord {
+        ghost: GhostIdentifier(0),
+        tick: DEATH,
+        event: PlayerKilledEvent {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+        },
+    });
+    let ghost_list = PlayerGhostList {
+        ghosts: vec![PlayerGhost {
+            entity: None,
+            class: Class::Ranger,
+            muted: false,
+        }],
+        forked: None,
+        forked_at: 0,
+    };
+    let mut file = ReplayFile::new(1, &ghost_list);
+    file.write_history(&actions);
+    file.write_history(&killed);
+    file
+}
+
+/// A headless app playing [`dying_ranger`] up to `tick`, that can seek in its cycle
+fn playing_app(tick: u64) -> App {
+    let mut app = headless_app();
+    app.add_event::<SeekEvent>()
+        .add_systems(Update, (seek_level, fast_forward).chain());
+    load_run(&mut app, &dying_ranger()).unwrap();
+    while app.world().resource::<LevelTick>().0 < tick {
+        app.update();
+    }
+    app
+}
+
+/// Seeks to `tick` and waits for the cycle to resume, returns the number of frames it took
+fn seek(app: &mut App, tick: u64) -> u64 {
+    app.world_mut().send_event(SeekEvent { tick });
+    let mut frames = 0;
+    while app.world().contains_resource::<FastForward>() || frames == 0 {
+        app.update();
+        frames += 1;
+        let now = app.world().resource::<LevelTick>().0;
+        assert!(now <= frames * FAST_FORWARD_STEPS);
+        // The cycle waits for the last frame of the fast-forward to resume
+        assert_eq!(
+            app.world().resource::<Time<Virtual>>().is_paused(),
+            now < tick
+        );
+    }
+    assert_eq!(app.world().resource::<LevelTick>().0, tick);
+    frames
+}
+
+#[test]
+fn seek_across_a_ghost_death() {
+    let mut app = playing_app(DEATH + 20);
+    assert_eq!(
+        app.world()
+            .resource::<LevelHistory<GhostKilledEvent>>()
+            .len(),
+        1
+    );
+
+    // The death is simulated again on the way to the sought tick
+    seek(&mut app, DEATH + 10);
+
+    // Still a single record, the one saved in the GDTH section
+    let deaths = app.world().resource::<LevelHistory<GhostKilledEvent>>();
+    assert_eq!(deaths.len(), 1);
+    assert_eq!(deaths.events()[0].tick, DEATH);
+}
+
+#[test]
+fn long_seek_is_spread_over_frames() {
+    let target = 3 * FAST_FORWARD_STEPS;
+    let mut app = playing_app(target + 100);
+
+    assert!(seek(&mut app, target) >= 3);
+    app.update();
+    assert_eq!(app.world().resource::<LevelTick>().0, target + 1);
+}
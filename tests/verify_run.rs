@@ -0,0 +1,105 @@
+//! Plays small runs of the first level through the headless verification
+
+use bevy::prelude::*;
+use cycle_of_the_fallen::level_history::prelude::*;
+use cycle_of_the_fallen::player::prelude::*;
+use cycle_of_the_fallen::verification::prelude::*;
+use cycle_of_the_fallen::verification::GRACE_TICKS;
+
+/// The first level has one dummy at (300, 100), the ghosts spawn at (-400, 0)
+const LEVEL: usize = 1;
+
+fn action(movement: Vec2, aim: Vec2, attack: bool) -> PlayerActionEvent {
+    PlayerActionEvent {
+        entity: Entity::PLACEHOLDER,
+        source: EventSource::Replay,
+        action: PlayerAction {
+            movement,
+            aim,
+            attack,
+            ability: false,
+        },
+    }
+}
+
+/// A file with a single ranger ghost playing the given actions
+fn ranger_run(actions: Vec<(u64, PlayerActionEvent)>) -> ReplayFile {
+    let mut history = LevelHistory::default();
+    for (tick, event) in actions {
+        history.push(EventRecord {
+            ghost: GhostIdentifier(0),
+            tick,
+            event,
+        });
+    }
+    let ghost_list = PlayerGhostList {
+        ghosts: vec![PlayerGhost {
+            entity: None,
+            class: Class::Ranger,
+            muted: false,
+        }],
+        forked: None,
+        forked_at: 0,
+    };
+    let mut file = ReplayFile::new(LEVEL, &ghost_list);
+    file.write_history(&history);
+    file
+}
+
+#[test]
+fn ranger_kills_the_dummy() {
+    // Walks toward the dummy until it is in range, then shoots it
+    let file = ranger_run(vec![
+        (1, action(Vec2::X, Vec2::X, false)),
+        (
+            144,
+            action(
+                Vec2::ZERO,
+                Vec2::new(300.0, 100.0) - Vec2::new(-240.0, 0.0),
+                false,
+            ),
+        ),
+        (
+            150,
+            action(
+                Vec2::ZERO,
+                Vec2::new(300.0, 100.0) - Vec2::new(-240.0, 0.0),
+                true,
+            ),
+        ),
+        (160, action(Vec2::ZERO, Vec2::ZERO, false)),
+    ]);
+
+    let report = verify_run(&file).unwrap();
+    assert!(report.completed(), "{report}");
+    assert_eq!(report.level, LEVEL);
+    assert_eq!(report.cycles, Some(1));
+    assert_eq!(report.kills.len(), 1);
+    assert_eq!(report.kills[0].ghost, Some(GhostIdentifier(0)));
+    assert!(report.casualties.is_empty());
+}
+
+#[test]
+fn ghost_dies_as_recorded() {
+    let mut file = ranger_run(vec![(1, action(Vec2::Y, Vec2::Y, false))]);
+    let mut killed = LevelHistory::default();
+    killed.push(EventRecord {
+        ghost: GhostIdentifier(0),
+        tick: 100,
+        event: PlayerKilledEvent {
+            entity: Entity::PLACEHOLDER,
+            source: EventSource::Replay,
+        },
+    });
+    file.write_history(&killed);
+
+    let report = verify_run(&file).unwrap();
+    assert!(!report.completed(), "{report}");
+    assert!(report.kills.is_empty());
+    assert_eq!(report.casualties.len(), 1);
+    assert_eq!(report.casualties[0].ghost, GhostIdentifier(0));
+    assert_eq!(report.casualties[0].killer, Killer::Recording);
+    assert!(report.ticks > 100 + GRACE_TICKS);
+    // Killed at the end of its recording, it did not diverge
+    assert!(report.divergences.is_empty());
+}